@@ -0,0 +1,554 @@
+//! Move by move analysis of a recorded game.
+//!
+//! Each move is compared with the best move found from the point of view of
+//! its player. Once no hidden card can be drawn and the player can only
+//! tell apart a few deals, each of them is solved exactly, see
+//! [`solver`](super::solver). Before that, moves are evaluated by a search on
+//! samples of the hidden cards, and the losses are estimates.
+
+use std::fmt::Write;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use thevalley_game::bot::Observation;
+use thevalley_game::cards::{Card, Deck, Hand};
+use thevalley_game::deal::DealState;
+use thevalley_game::pos::PlayerPos;
+use thevalley_game::record::{GameRecord, RecordError};
+use thevalley_game::{deal_seed, deal_seeded_hands, Seed};
+
+use super::ismcts::{search, SearchConfig};
+use super::sampling::HiddenCards;
+use super::solver::{SolveError, Solver};
+
+/// Number of possible deals from which a position is left to the search
+/// rather than solved for each deal.
+const MAX_SOLVED_DEALS: u64 = 256;
+
+/// Settings of an analysis.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnalysisConfig {
+    /// Search used while some cards are hidden
+    pub search: SearchConfig,
+    /// Number of positions the solver may visit for a move before falling
+    /// back to the search
+    pub solver_nodes: u64,
+    /// Points lost from which a move is a mistake
+    pub mistake: f32,
+    /// Points lost from which a move is a blunder
+    pub blunder: f32,
+    /// Seed of the samples, the same seed gives the same analysis
+    pub seed: Seed,
+}
+
+impl Default for AnalysisConfig {
+    fn default() -> Self {
+        AnalysisConfig {
+            search: SearchConfig {
+                iterations: 1000,
+                ..SearchConfig::default()
+            },
+            solver_nodes: 200_000,
+            mistake: 1.0,
+            blunder: 3.0,
+            seed: [0; 32],
+        }
+    }
+}
+
+/// How a move compares with the best one.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Judgement {
+    /// The only legal move
+    Forced,
+    Best,
+    /// Loses less than a mistake
+    Good,
+    Mistake,
+    Blunder,
+}
+
+impl Judgement {
+    pub fn name(self) -> &'static str {
+        match self {
+            Judgement::Forced => "forced",
+            Judgement::Best => "best",
+            Judgement::Good => "good",
+            Judgement::Mistake => "mistake",
+            Judgement::Blunder => "blunder",
+        }
+    }
+}
+
+/// How the moves of a position were evaluated.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Method {
+    /// Only one move was legal
+    None,
+    /// The points are exact, averaged over the deals the player can't tell apart
+    Solver,
+    /// Hidden cards were sampled, the points are estimates
+    Search,
+}
+
+/// Analysis of a recorded move.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct MoveAnalysis {
+    /// Index of the deal in the record
+    pub deal: usize,
+    /// Number of the trick in the deal, from 1
+    pub trick: usize,
+    pub pos: PlayerPos,
+    /// Card played
+    pub card: Card,
+    /// Points the player could expect at the end of the deal after `card`
+    pub expected: f32,
+    /// Best card to play
+    pub best: Card,
+    /// Points the player could expect at the end of the deal after `best`
+    pub best_expected: f32,
+    /// Points lost by not playing `best`
+    pub loss: f32,
+    pub judgement: Judgement,
+    pub method: Method,
+}
+
+/// Totals of the analysis for a player.
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PlayerSummary {
+    pub nickname: String,
+    pub mistakes: usize,
+    pub blunders: usize,
+    /// Sum of the points lost by every move
+    pub loss: f32,
+}
+
+/// Analysis of every move of a game.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct GameAnalysis {
+    pub players: Vec<PlayerSummary>,
+    pub moves: Vec<MoveAnalysis>,
+}
+
+impl GameAnalysis {
+    /// Returns the JSON form of the analysis.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("analysis can be serialized")
+    }
+
+    /// Returns a report of the analysis, to be read by the players.
+    ///
+    /// Moves losing points are followed by the best card to play instead and
+    /// the points lost; `~` marks estimated losses.
+    pub fn to_text(&self) -> String {
+        let mut s = String::new();
+        let mut deal = None;
+        for analysis in self.moves.iter() {
+            if deal != Some(analysis.deal) {
+                deal = Some(analysis.deal);
+                let _ = writeln!(s, "{}Deal {}", if s.is_empty() { "" } else { "\n" }, analysis.deal + 1);
+            }
+            let nickname = self.players.get(analysis.pos.to_n()).map_or("", |p| p.nickname.as_str());
+            let _ = write!(
+                s,
+                "{:>3}. {:?} {:<12} {:<4} {}",
+                analysis.trick,
+                analysis.pos,
+                nickname,
+                analysis.card.to_string(),
+                analysis.judgement.name()
+            );
+            if analysis.loss > 0.0 {
+                let estimate = if analysis.method == Method::Search { "~" } else { "" };
+                let _ = write!(s, " ({} was better, {}{:.1} points)", analysis.best, estimate, analysis.loss);
+            }
+            s.push('\n');
+        }
+
+        s.push('\n');
+        for player in self.players.iter() {
+            let _ = writeln!(
+                s,
+                "{}: {} mistake(s), {} blunder(s), {:.1} points lost",
+                player.nickname, player.mistakes, player.blunders, player.loss
+            );
+        }
+        s
+    }
+}
+
+/// Analyses every move of `record`.
+///
+/// The record is replayed first, an illegal play gives an error.
+pub fn analyse(record: &GameRecord, config: &AnalysisConfig) -> Result<GameAnalysis, RecordError> {
+    record.replay()?;
+
+    let nb_players = record.nb_players();
+    let mut players: Vec<PlayerSummary> = record
+        .seats
+        .iter()
+        .map(|nickname| PlayerSummary {
+            nickname: nickname.clone(),
+            ..PlayerSummary::default()
+        })
+        .collect();
+    let mut moves = vec![];
+    for (deal_idx, deal_record) in record.deals.iter().enumerate() {
+        let mut rng = StdRng::from_seed(deal_seed(&config.seed, deal_idx));
+        let mut solver = Solver::with_node_limit(config.solver_nodes);
+        let (hands, stock) = deal_seeded_hands(deal_seed(&record.seed, deal_idx), nb_players, &record.rules);
+        let mut state = DealState::new(deal_record.first, hands, stock, record.rules.clone());
+        for play in deal_record.plays.iter() {
+            let analysis = analyse_move(&state, play.card, deal_idx, config, &mut solver, &mut rng);
+            let player = &mut players[play.pos.to_n()];
+            player.loss += analysis.loss;
+            match analysis.judgement {
+                Judgement::Mistake => player.mistakes += 1,
+                Judgement::Blunder => player.blunders += 1,
+                _ => (),
+            }
+            moves.push(analysis);
+            state.play_card(play.pos, play.card).expect("record was replayed");
+        }
+    }
+    Ok(GameAnalysis { players, moves })
+}
+
+fn analyse_move(
+    state: &DealState,
+    card: Card,
+    deal: usize,
+    config: &AnalysisConfig,
+    solver: &mut Solver,
+    rng: &mut StdRng,
+) -> MoveAnalysis {
+    let pos = state.next_player();
+    let (values, method) = evaluate(state, config, solver, rng);
+    let (best, best_expected) = values[0];
+    // The search may not have tried every move with too few iterations
+    let expected = values
+        .iter()
+        .find(|(c, _)| *c == card)
+        .map_or(values[values.len() - 1].1, |(_, value)| *value);
+    let loss = if card == best { 0.0 } else { (best_expected - expected).max(0.0) };
+
+    let judgement = if method == Method::None {
+        Judgement::Forced
+    } else if card == best || loss == 0.0 {
+        // Ties are as good as the best move
+        Judgement::Best
+    } else if loss < config.mistake {
+        Judgement::Good
+    } else if loss < config.blunder {
+        Judgement::Mistake
+    } else {
+        Judgement::Blunder
+    };
+
+    MoveAnalysis {
+        deal,
+        trick: state.tricks().len(),
+        pos,
+        card,
+        expected,
+        best,
+        best_expected,
+        loss,
+        judgement,
+        method,
+    }
+}
+
+/// Returns the points the player to move can expect after each of its legal
+/// moves, from the best one.
+fn evaluate(state: &DealState, config: &AnalysisConfig, solver: &mut Solver, rng: &mut StdRng) -> (Vec<(Card, f32)>, Method) {
+    let pos = state.next_player();
+    let observation = Observation::new(state, pos);
+    if observation.legal_moves.size() == 1 {
+        let card = observation.legal_moves.get_card();
+        return (vec![(card, 0.0)], Method::None);
+    }
+
+    if let Some(deals) = possible_deals(&observation) {
+        match solve_deals(&deals, solver) {
+            Ok(values) => return (values, Method::Solver),
+            Err(SolveError::NodeLimit) => (),
+            Err(SolveError::DealOver) => unreachable!("a move is played"),
+        }
+    }
+
+    let total = deal_points(state);
+    let values = search(&observation, &config.search, rng)
+        .into_iter()
+        .map(|score| (score.card, score.score as f32 * total))
+        .collect();
+    (values, Method::Search)
+}
+
+/// Returns every deal consistent with what the player sees, if they are few.
+///
+/// This is only done once no card can be drawn anymore: the unseen cards left
+/// in the stock never come into play, and the deals only differ by the hands
+/// of the other players.
+fn possible_deals(observation: &Observation) -> Option<Vec<DealState>> {
+    if observation.stock_size >= observation.nb_players() {
+        return None;
+    }
+    let hidden = HiddenCards::new(observation);
+    let me = observation.pos.to_n();
+    let others: Vec<usize> = (0..observation.nb_players())
+        .filter(|n| *n != me && hidden.hand_sizes()[*n] > 0)
+        .collect();
+    let mut hands = vec![Hand::new(); observation.nb_players()];
+    hands[me] = observation.hand;
+    let mut splits = vec![];
+    if !split_unseen(&hidden, &others, hidden.unseen(), &mut hands, &mut splits) {
+        return None;
+    }
+
+    let deals = splits
+        .into_iter()
+        .map(|(hands, left)| {
+            // Never drawn, any of the cards left will do
+            let stock = left.list().into_iter().take(observation.stock_size).collect();
+            DealState::from_position(hands, Deck::from_cards(stock), observation.tricks.clone(), observation.rules.clone())
+        })
+        .collect();
+    Some(deals)
+}
+
+/// Adds to `splits` every way to deal the cards `left` to the players
+/// `others`, with the cards left over, respecting their voids.
+///
+/// Returns `false` if there are more than `MAX_SOLVED_DEALS` ways.
+fn split_unseen(
+    hidden: &HiddenCards,
+    others: &[usize],
+    left: Hand,
+    hands: &mut Vec<Hand>,
+    splits: &mut Vec<(Vec<Hand>, Hand)>,
+) -> bool {
+    let (n, others) = match others.split_first() {
+        Some((n, others)) => (*n, others),
+        None => {
+            splits.push((hands.clone(), left));
+            return splits.len() as u64 <= MAX_SOLVED_DEALS;
+        }
+    };
+    let candidates = (left - hidden.voids(n)).list();
+    let size = hidden.hand_sizes()[n];
+    if nb_subsets(candidates.len(), size) > MAX_SOLVED_DEALS {
+        return false;
+    }
+    for hand in subsets(&candidates, size) {
+        hands[n] = hand;
+        if !split_unseen(hidden, others, left - hand, hands, splits) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Returns the number of subsets of `size` elements among `n`, or a number
+/// above `MAX_SOLVED_DEALS` if there are more.
+fn nb_subsets(n: usize, size: usize) -> u64 {
+    if n < size {
+        return 0;
+    }
+    let mut count = 1;
+    for i in 0..size as u64 {
+        count = count * (n as u64 - i) / (i + 1);
+        if count > MAX_SOLVED_DEALS {
+            break;
+        }
+    }
+    count
+}
+
+/// Returns the hands of `size` cards among `cards`.
+fn subsets(cards: &[Card], size: usize) -> Vec<Hand> {
+    if size == 0 {
+        return vec![Hand::new()];
+    }
+    if cards.len() < size {
+        return vec![];
+    }
+    let mut hands = subsets(&cards[1..], size - 1);
+    for hand in hands.iter_mut() {
+        hand.add(cards[0]);
+    }
+    hands.extend(subsets(&cards[1..], size));
+    hands
+}
+
+/// Returns the final points of the player to move after each of its legal
+/// moves, from the best one, averaged over `deals`.
+fn solve_deals(deals: &[DealState], solver: &mut Solver) -> Result<Vec<(Card, f32)>, SolveError> {
+    let mut totals: Vec<(Card, f32)> = vec![];
+    for deal in deals {
+        for value in solver.evaluate_moves(deal)? {
+            match totals.iter_mut().find(|(card, _)| *card == value.card) {
+                Some((_, total)) => *total += value.score,
+                None => totals.push((value.card, value.score)),
+            }
+        }
+    }
+    let mut values: Vec<(Card, f32)> = totals
+        .into_iter()
+        .map(|(card, total)| (card, total / deals.len() as f32))
+        .collect();
+    values.sort_by(|a, b| b.1.partial_cmp(&a.1).expect("points are numbers"));
+    Ok(values)
+}
+
+/// Returns the total points of the deal, which don't depend on the moves.
+fn deal_points(state: &DealState) -> f32 {
+    let mut state = state.clone();
+    while !state.is_over() {
+        let pos = state.next_player();
+        let card = state.legal_moves(pos).get_card();
+        state.play_card(pos, card).expect("legal move");
+    }
+    state.points().iter().sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use thevalley_game::cards::{Deck, Hand, Rank, Suit};
+    use thevalley_game::record::{DealRecord, PlayRecord};
+    use thevalley_game::rules::RuleSet;
+
+    /// Records a 2 players deal where P0 plays its weakest card and P1 the
+    /// first legal card.
+    fn record() -> GameRecord {
+        let seats = vec!["alice".to_owned(), "bob".to_owned()];
+        let mut record = GameRecord::new(RuleSet::default(), [5; 32], seats);
+        let (hands, stock) = deal_seeded_hands(deal_seed(&record.seed, 0), 2, &record.rules);
+        let mut state = DealState::new(PlayerPos::P0, hands, stock, record.rules.clone());
+        let mut deal_record = DealRecord::new(PlayerPos::P0);
+        while !state.is_over() {
+            let pos = state.next_player();
+            let moves = state.legal_moves(pos);
+            let card = if pos == PlayerPos::P0 {
+                moves.min_by_strength(|c| state.rules().strength(c)).unwrap()
+            } else {
+                moves.get_card()
+            };
+            state.play_card(pos, card).unwrap();
+            deal_record.plays.push(PlayRecord { pos, card });
+        }
+        record.deals.push(deal_record);
+        record
+    }
+
+    fn config() -> AnalysisConfig {
+        AnalysisConfig {
+            search: SearchConfig {
+                iterations: 50,
+                ..SearchConfig::default()
+            },
+            solver_nodes: 20_000,
+            ..AnalysisConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_analyse() {
+        let record = record();
+        let analysis = analyse(&record, &config()).unwrap();
+        assert_eq!(analysis.moves.len(), record.deals[0].plays.len());
+        assert_eq!(analysis.moves[0].trick, 1);
+        assert_eq!(analysis.moves[2].trick, 2);
+
+        for (analysis, play) in analysis.moves.iter().zip(record.deals[0].plays.iter()) {
+            assert_eq!((analysis.pos, analysis.card), (play.pos, play.card));
+            assert!(analysis.loss >= 0.0);
+            if analysis.card == analysis.best {
+                assert_eq!(analysis.loss, 0.0);
+                assert!(analysis.judgement == Judgement::Best || analysis.judgement == Judgement::Forced);
+            }
+            if analysis.judgement == Judgement::Forced {
+                assert_eq!(analysis.method, Method::None);
+            }
+        }
+        // The stock ends before the hands: the last tricks are solved
+        let last = analysis.moves.iter().rev().find(|m| m.judgement != Judgement::Forced).unwrap();
+        assert_eq!(last.method, Method::Solver);
+
+        for (n, player) in analysis.players.iter().enumerate() {
+            let moves = analysis.moves.iter().filter(|m| m.pos.to_n() == n);
+            let loss: f32 = moves.clone().map(|m| m.loss).sum();
+            assert_eq!(player.loss, loss);
+            let blunders = moves.filter(|m| m.judgement == Judgement::Blunder).count();
+            assert_eq!(player.blunders, blunders);
+        }
+
+        let text = analysis.to_text();
+        assert!(text.starts_with("Deal 1\n  1. P0 alice"));
+        assert!(text.contains("bob: "));
+        let json: GameAnalysis = serde_json::from_str(&analysis.to_json()).unwrap();
+        assert_eq!(json, analysis);
+
+        assert_eq!(analyse(&record, &config()), Ok(analysis));
+    }
+
+    #[test]
+    fn test_solved_blunder() {
+        // P1 gives away the king and the last trick instead of winning them
+        let heart_k = Card::new(Suit::Heart, Rank::RankK);
+        let heart_9 = Card::new(Suit::Heart, Rank::Rank9);
+        let heart_2 = Card::new(Suit::Heart, Rank::Rank2);
+        let mut hands = vec![Hand::new(); 2];
+        hands[0].add(heart_k).add(Card::new(Suit::Spade, Rank::Rank3));
+        hands[1].add(heart_9).add(heart_2);
+        let rules = RuleSet { hand_size: 2, ..RuleSet::default() };
+        let mut state = DealState::new(PlayerPos::P0, hands, Deck::empty(), rules);
+        state.play_card(PlayerPos::P0, heart_k).unwrap();
+
+        let mut solver = Solver::new();
+        let mut rng = StdRng::from_seed([0; 32]);
+        let analysis = analyse_move(&state, heart_2, 0, &config(), &mut solver, &mut rng);
+        assert_eq!(analysis.method, Method::Solver);
+        assert_eq!(analysis.best, heart_9);
+        // The last card of P0 could be any unseen card, it sometimes wins the last trick
+        assert!(analysis.expected > 0.0 && analysis.expected < 1.0);
+        assert_eq!(analysis.loss, 5.0);
+        assert_eq!(analysis.judgement, Judgement::Blunder);
+    }
+
+    #[test]
+    fn test_solved_with_cards_left_in_stock() {
+        // With 4 players the stock is never emptied: the last player of the
+        // trick before the last one still knows enough
+        let rules = RuleSet::default();
+        let (hands, stock) = deal_seeded_hands([3; 32], 4, &rules);
+        let mut state = DealState::new(PlayerPos::P0, hands, stock, rules);
+        while state.hands()[state.next_player() as usize].size() > 2
+            || state.current_trick().cards.iter().flatten().count() < 3
+        {
+            let pos = state.next_player();
+            let card = state.legal_moves(pos).get_card();
+            state.play_card(pos, card).unwrap();
+        }
+        assert!(state.stock_size() > 0);
+
+        let observation = Observation::new(&state, state.next_player());
+        let deals = possible_deals(&observation).unwrap();
+        assert!(deals.len() > 1);
+        assert!(deals.iter().any(|deal| deal.hands() == state.hands()));
+        for deal in &deals {
+            assert_eq!(deal.stock_size(), state.stock_size());
+            assert_eq!(deal.legal_moves(observation.pos), observation.legal_moves);
+        }
+
+        let mut rng = StdRng::from_seed([0; 32]);
+        let (values, method) = evaluate(&state, &config(), &mut Solver::new(), &mut rng);
+        assert_eq!(method, Method::Solver);
+        assert_eq!(values.len(), observation.legal_moves.size());
+    }
+}
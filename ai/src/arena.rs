@@ -0,0 +1,291 @@
+//! Matches between two strategies, to compare them.
+//!
+//! Each deal is played twice, the strategies swapping their seats, so that
+//! none of them gets the better cards. Deals come from `deal_seeded_hands`:
+//! the same seed always gives the same match.
+
+use std::fmt;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use thevalley_game::bot::{Observation, Strategy};
+use thevalley_game::cards::Card;
+use thevalley_game::deal::{DealState, PlayError};
+use thevalley_game::pos::PlayerPos;
+use thevalley_game::rules::{RuleSet, RulesError};
+use thevalley_game::{deal_seed, deal_seeded_hands, Seed};
+
+/// Settings of a match.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArenaConfig {
+    /// Number of deals, each of them is played twice
+    pub deals: usize,
+    /// Seed of the deals and of the strategies
+    pub seed: Seed,
+    pub rules: RuleSet,
+}
+
+impl Default for ArenaConfig {
+    fn default() -> Self {
+        ArenaConfig {
+            deals: 100,
+            seed: [0; 32],
+            rules: RuleSet::default(),
+        }
+    }
+}
+
+/// Error stopping a match.
+#[derive(PartialEq, Debug)]
+pub enum ArenaError {
+    /// The rules can't be played by two players
+    Rules(RulesError),
+    /// A strategy chose a card it was not allowed to play
+    IllegalMove {
+        deal: usize,
+        /// Index of the strategy, 0 for the first one
+        player: usize,
+        card: Card,
+        error: PlayError,
+    },
+}
+
+impl fmt::Display for ArenaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArenaError::Rules(error) => write!(f, "rules: {}", error),
+            ArenaError::IllegalMove { deal, player, card, error } => {
+                write!(f, "deal {}: player {} played {}: {}", deal + 1, player + 1, card, error)
+            }
+        }
+    }
+}
+
+/// Mean of samples, with its 95% confidence interval.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Estimate {
+    count: usize,
+    sum: f64,
+    squares: f64,
+}
+
+impl Estimate {
+    pub fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.squares += value * value;
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+
+    /// Returns the half width of the 95% confidence interval of the mean.
+    pub fn interval(&self) -> f64 {
+        if self.count < 2 {
+            return 0.0;
+        }
+        let n = self.count as f64;
+        let variance = (self.squares - self.sum * self.sum / n) / (n - 1.0);
+        1.96 * (variance.max(0.0) / n).sqrt()
+    }
+}
+
+/// Results of a strategy in a match.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlayerStats {
+    pub wins: usize,
+    /// 1 for a win, 0.5 for a draw, 0 for a loss
+    pub score: Estimate,
+    /// Points won in each game
+    pub points: Estimate,
+    pub moves: usize,
+    /// Time spent choosing the moves
+    pub time: Duration,
+}
+
+/// Results of a match.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArenaResult {
+    pub games: usize,
+    pub draws: usize,
+    pub players: [PlayerStats; 2],
+    pub elapsed: Duration,
+}
+
+impl ArenaResult {
+    /// Returns the share of the games won by the `n`-th strategy.
+    pub fn win_rate(&self, n: usize) -> f64 {
+        if self.games == 0 {
+            0.0
+        } else {
+            self.players[n].wins as f64 / self.games as f64
+        }
+    }
+
+    pub fn games_per_second(&self) -> f64 {
+        self.games as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    pub fn moves_per_second(&self) -> f64 {
+        let moves = self.players[0].moves + self.players[1].moves;
+        moves as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Returns a report of the match, `names` being the names of the strategies.
+    pub fn to_text(&self, names: [&str; 2]) -> String {
+        let mut s = format!(
+            "{} games on {} deals in {:.1}s: {:.1} games/s, {:.0} moves/s\n",
+            self.games,
+            self.games / 2,
+            self.elapsed.as_secs_f64(),
+            self.games_per_second(),
+            self.moves_per_second()
+        );
+        for (n, (name, stats)) in names.iter().zip(self.players.iter()).enumerate() {
+            let per_move = if stats.moves == 0 {
+                0.0
+            } else {
+                stats.time.as_secs_f64() * 1000.0 / stats.moves as f64
+            };
+            let _ = writeln!(
+                s,
+                "{:<10} wins {:>5.1}%  score {:>5.1}% ± {:.1}%  points {:.2} ± {:.2}  {:.2} ms/move",
+                name,
+                self.win_rate(n) * 100.0,
+                stats.score.mean() * 100.0,
+                stats.score.interval() * 100.0,
+                stats.points.mean(),
+                stats.points.interval(),
+                per_move
+            );
+        }
+        let draws = if self.games == 0 { 0.0 } else { self.draws as f64 / self.games as f64 };
+        let _ = writeln!(s, "draws {:.1}%", draws * 100.0);
+        s
+    }
+}
+
+/// Plays `config.deals` deals twice between the strategies built by `first`
+/// and `second`.
+///
+/// A new player is built for each game from a seed given to the function.
+pub fn play_match<A, B>(config: &ArenaConfig, mut first: A, mut second: B) -> Result<ArenaResult, ArenaError>
+where
+    A: FnMut(Seed) -> Box<dyn Strategy + Send>,
+    B: FnMut(Seed) -> Box<dyn Strategy + Send>,
+{
+    config.rules.validate(2).map_err(ArenaError::Rules)?;
+
+    let start = Instant::now();
+    let mut result = ArenaResult {
+        games: 0,
+        draws: 0,
+        players: [PlayerStats::default(), PlayerStats::default()],
+        elapsed: Duration::default(),
+    };
+    for deal in 0..config.deals {
+        let seed = deal_seed(&config.seed, deal);
+        for &swapped in &[false, true] {
+            let mut strategies = [first(deal_seed(&seed, 1)), second(deal_seed(&seed, 2))];
+            // Index of the strategy at each seat
+            let seats = if swapped { [1, 0] } else { [0, 1] };
+
+            let (hands, stock) = deal_seeded_hands(seed, 2, &config.rules);
+            let mut state = DealState::new(PlayerPos::P0, hands, stock, config.rules.clone());
+            while !state.is_over() {
+                let pos = state.next_player();
+                let player = seats[pos.to_n()];
+                let observation = Observation::new(&state, pos);
+                let move_start = Instant::now();
+                let card = strategies[player].play(&observation);
+                let stats = &mut result.players[player];
+                stats.time += move_start.elapsed();
+                stats.moves += 1;
+                state
+                    .play_card(pos, card)
+                    .map_err(|error| ArenaError::IllegalMove { deal, player, card, error })?;
+            }
+
+            let points = state.points();
+            let mut game_points = [0.0; 2];
+            for (seat, player) in seats.iter().enumerate() {
+                game_points[*player] = points[seat];
+            }
+            let scores = if game_points[0] > game_points[1] {
+                [1.0, 0.0]
+            } else if game_points[0] < game_points[1] {
+                [0.0, 1.0]
+            } else {
+                result.draws += 1;
+                [0.5, 0.5]
+            };
+            for (n, stats) in result.players.iter_mut().enumerate() {
+                if scores[n] == 1.0 {
+                    stats.wins += 1;
+                }
+                stats.score.add(scores[n]);
+                stats.points.add(f64::from(game_points[n]));
+            }
+            result.games += 1;
+        }
+    }
+    result.elapsed = start.elapsed();
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use thevalley_game::bot::{GreedyStrategy, SeededRandomStrategy};
+
+    fn greedy(_seed: Seed) -> Box<dyn Strategy + Send> {
+        Box::new(GreedyStrategy)
+    }
+
+    fn random(seed: Seed) -> Box<dyn Strategy + Send> {
+        Box::new(SeededRandomStrategy::new(seed))
+    }
+
+    #[test]
+    fn test_mirror_match() {
+        // Greedy players make the same choices from both seats
+        let config = ArenaConfig { deals: 5, ..ArenaConfig::default() };
+        let result = play_match(&config, greedy, greedy).unwrap();
+        assert_eq!(result.games, 10);
+        assert_eq!(result.players[0].wins, result.players[1].wins);
+        assert_eq!(result.players[0].points.mean(), result.players[1].points.mean());
+        assert_eq!(result.players[0].score.mean(), 0.5);
+        assert_eq!(result.players[0].moves + result.players[1].moves, 10 * 54);
+    }
+
+    #[test]
+    fn test_reproducible() {
+        let config = ArenaConfig { deals: 10, seed: [7; 32], ..ArenaConfig::default() };
+        let result = play_match(&config, greedy, random).unwrap();
+        let other = play_match(&config, greedy, random).unwrap();
+        assert_eq!(result.players[0].wins, other.players[0].wins);
+        assert_eq!(result.players[1].points, other.players[1].points);
+        assert!(result.win_rate(0) > result.win_rate(1));
+        assert!(result.players[0].points.interval() > 0.0);
+
+        let text = result.to_text(["greedy", "random"]);
+        assert!(text.starts_with("20 games on 10 deals"));
+        assert!(text.contains("\nrandom "));
+    }
+
+    #[test]
+    fn test_estimate() {
+        let mut estimate = Estimate::default();
+        for value in &[1.0, 2.0, 3.0, 4.0] {
+            estimate.add(*value);
+        }
+        assert_eq!(estimate.mean(), 2.5);
+        // Standard deviation of 1.29, over the square root of 4
+        assert!((estimate.interval() - 1.96 * 1.2910 / 2.0).abs() < 1e-3);
+    }
+}
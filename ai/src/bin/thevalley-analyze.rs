@@ -0,0 +1,54 @@
+//! Prints the analysis of a recorded game.
+//!
+//! The record is read in its text form, or as JSON if it starts with `{`.
+//!
+//! Built with the `cli` feature: `cargo run -p thevalley_ai --features cli --bin thevalley-analyze`.
+
+use std::fs;
+use std::process;
+
+use clap::{App, Arg};
+
+use thevalley_ai::analysis::{analyse, AnalysisConfig};
+use thevalley_game::record::GameRecord;
+
+fn main() {
+    let matches = App::new("thevalley-analyze")
+        .about("Analyses each move of a recorded game")
+        .arg(Arg::with_name("RECORD").help("File of the game record").required(true))
+        .arg(Arg::with_name("json").long("json").help("Prints the analysis as JSON"))
+        .arg(
+            Arg::with_name("iterations")
+                .long("iterations")
+                .takes_value(true)
+                .help("Number of iterations of the search while cards are hidden"),
+        )
+        .get_matches();
+
+    let path = matches.value_of("RECORD").expect("required argument");
+    let content = fs::read_to_string(path).unwrap_or_else(|e| exit(&format!("can't read {}: {}", path, e)));
+    let record = if content.trim_start().starts_with('{') {
+        serde_json::from_str(&content).unwrap_or_else(|e| exit(&format!("invalid record: {}", e)))
+    } else {
+        GameRecord::from_text(&content).unwrap_or_else(|e| exit(&format!("invalid record: {}", e)))
+    };
+
+    let mut config = AnalysisConfig::default();
+    if let Some(iterations) = matches.value_of("iterations") {
+        config.search.iterations = iterations
+            .parse()
+            .unwrap_or_else(|_| exit("iterations must be a number"));
+    }
+
+    let analysis = analyse(&record, &config).unwrap_or_else(|e| exit(&format!("invalid record: {}", e)));
+    if matches.is_present("json") {
+        println!("{}", analysis.to_json());
+    } else {
+        print!("{}", analysis.to_text());
+    }
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("thevalley-analyze: {}", message);
+    process::exit(1);
+}
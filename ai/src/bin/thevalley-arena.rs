@@ -0,0 +1,72 @@
+//! Plays two strategies against each other and prints the results.
+//!
+//! ```text
+//! thevalley-arena greedy ismcts --deals 200 --iterations 500
+//! ```
+//!
+//! Built with the `cli` feature: `cargo run -p thevalley_ai --features cli --bin thevalley-arena`.
+
+use std::process;
+
+use clap::{App, Arg, ArgMatches};
+
+use thevalley_ai::arena::{play_match, ArenaConfig};
+use thevalley_ai::{seeded_strategy, SearchConfig, StrategyKind};
+use thevalley_game::parse_seed;
+
+fn main() {
+    let matches = App::new("thevalley-arena")
+        .about("Plays two strategies against each other, swapping seats on each deal")
+        .arg(Arg::with_name("FIRST").help("First strategy: random, greedy or ismcts").required(true))
+        .arg(Arg::with_name("SECOND").help("Second strategy").required(true))
+        .arg(
+            Arg::with_name("deals")
+                .long("deals")
+                .takes_value(true)
+                .help("Number of deals, each of them is played twice"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .help("Seed of the deals, as 64 hexadecimal digits"),
+        )
+        .arg(
+            Arg::with_name("iterations")
+                .long("iterations")
+                .takes_value(true)
+                .help("Number of iterations of the ismcts search"),
+        )
+        .get_matches();
+
+    let kinds = [strategy_kind(&matches, "FIRST"), strategy_kind(&matches, "SECOND")];
+    let mut config = ArenaConfig::default();
+    if let Some(deals) = matches.value_of("deals") {
+        config.deals = deals.parse().unwrap_or_else(|_| exit("deals must be a number"));
+    }
+    if let Some(seed) = matches.value_of("seed") {
+        config.seed = parse_seed(seed).unwrap_or_else(|| exit("invalid seed"));
+    }
+    let mut search = SearchConfig::default();
+    if let Some(iterations) = matches.value_of("iterations") {
+        search.iterations = iterations.parse().unwrap_or_else(|_| exit("iterations must be a number"));
+    }
+
+    let result = play_match(
+        &config,
+        |seed| seeded_strategy(kinds[0], &search, seed),
+        |seed| seeded_strategy(kinds[1], &search, seed),
+    )
+    .unwrap_or_else(|e| exit(&e.to_string()));
+    print!("{}", result.to_text([kinds[0].name(), kinds[1].name()]));
+}
+
+fn strategy_kind(matches: &ArgMatches, arg: &str) -> StrategyKind {
+    let name = matches.value_of(arg).expect("required argument");
+    StrategyKind::from_name(name).unwrap_or_else(|| exit(&format!("unknown strategy {}", name)))
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("thevalley-arena: {}", message);
+    process::exit(1);
+}
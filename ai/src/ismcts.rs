@@ -0,0 +1,272 @@
+//! Information set Monte Carlo tree search.
+//!
+//! Each iteration samples the hidden cards, then walks down a single tree
+//! shared by all the samples: only the moves legal in the current sample are
+//! considered. The tree holds the moves of every player, each of them trying
+//! to maximize its own share of the deal points.
+
+use rand::rngs::StdRng;
+use rand::seq::IteratorRandom;
+use rand::{Rng, SeedableRng};
+
+use thevalley_game::bot::{Observation, Strategy};
+use thevalley_game::cards::{Card, Hand};
+use thevalley_game::deal::DealState;
+use thevalley_game::pos::PlayerPos;
+use thevalley_game::Seed;
+
+use super::sampling::HiddenCards;
+
+/// Settings of a search.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchConfig {
+    /// Number of sampled deals played until the end
+    pub iterations: usize,
+    /// Weight of the less visited moves in the selection (UCB1 constant)
+    pub exploration: f64,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            iterations: 2000,
+            exploration: 0.7,
+        }
+    }
+}
+
+/// Evaluation of a move by the search.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveScore {
+    pub card: Card,
+    /// Number of iterations which started with this move
+    pub visits: u32,
+    /// Average share of the deal points won after this move, between 0 and 1
+    pub score: f64,
+}
+
+struct Node {
+    /// Card played to reach this node, `None` for the root
+    card: Option<Card>,
+    /// Player who played `card`
+    player: PlayerPos,
+    children: Vec<usize>,
+    visits: u32,
+    /// Number of times this node could have been chosen
+    availability: u32,
+    reward: f64,
+}
+
+impl Node {
+    fn new(card: Option<Card>, player: PlayerPos) -> Self {
+        Node {
+            card,
+            player,
+            children: vec![],
+            visits: 0,
+            availability: 1,
+            reward: 0.0,
+        }
+    }
+
+    fn ucb(&self, exploration: f64) -> f64 {
+        let visits = f64::from(self.visits);
+        self.reward / visits + exploration * (f64::from(self.availability).ln() / visits).sqrt()
+    }
+}
+
+/// Returns the share of the points of each player at the end of `state`.
+fn rewards(state: &DealState) -> Vec<f64> {
+    let points = state.points();
+    let total: f32 = points.iter().sum();
+    points
+        .iter()
+        .map(|p| if total > 0.0 { f64::from(*p / total) } else { 1.0 / points.len() as f64 })
+        .collect()
+}
+
+/// Evaluates the legal moves of `observation`.
+///
+/// Moves are sorted from the most visited, which is the one to play.
+pub fn search<R: Rng + ?Sized>(observation: &Observation, config: &SearchConfig, rng: &mut R) -> Vec<MoveScore> {
+    let hidden = HiddenCards::new(observation);
+    let mut nodes = vec![Node::new(None, observation.pos)];
+
+    for _ in 0..config.iterations.max(1) {
+        let mut state = hidden.sample(observation, rng);
+        let mut path = vec![0];
+        let mut current = 0;
+
+        // Selection, until a move was never tried
+        while !state.is_over() {
+            let pos = state.next_player();
+            let moves = state.legal_moves(pos);
+            let mut tried = Hand::new();
+            let mut best: Option<(usize, f64)> = None;
+            for i in 0..nodes[current].children.len() {
+                let child = nodes[current].children[i];
+                let node = &mut nodes[child];
+                let card = node.card.expect("only the root has no card");
+                if !moves.has(card) {
+                    continue;
+                }
+                tried.add(card);
+                node.availability += 1;
+                let value = node.ucb(config.exploration);
+                if best.is_none() || best.unwrap().1 < value {
+                    best = Some((child, value));
+                }
+            }
+
+            let untried = moves - tried;
+            let next = if let Some(card) = untried.iter().choose(rng) {
+                nodes.push(Node::new(Some(card), pos));
+                let child = nodes.len() - 1;
+                nodes[current].children.push(child);
+                child
+            } else {
+                best.expect("a legal move was tried").0
+            };
+            let card = nodes[next].card.expect("only the root has no card");
+            state.play_card(pos, card).expect("sampled move is legal");
+            path.push(next);
+            current = next;
+            if nodes[next].visits == 0 {
+                break;
+            }
+        }
+
+        // Random playout
+        while !state.is_over() {
+            let pos = state.next_player();
+            let card = state.legal_moves(pos).iter().choose(rng).expect("no legal move");
+            state.play_card(pos, card).expect("random move is legal");
+        }
+
+        let rewards = rewards(&state);
+        for n in path {
+            let node = &mut nodes[n];
+            node.visits += 1;
+            node.reward += rewards[node.player as usize];
+        }
+    }
+
+    let mut scores: Vec<MoveScore> = nodes[0]
+        .children
+        .iter()
+        .map(|&child| {
+            let node = &nodes[child];
+            MoveScore {
+                card: node.card.expect("only the root has no card"),
+                visits: node.visits,
+                score: node.reward / f64::from(node.visits),
+            }
+        })
+        .collect();
+    scores.sort_by_key(|score| std::cmp::Reverse(score.visits));
+    scores
+}
+
+/// Plays the move found by `search`.
+pub struct IsmctsStrategy {
+    config: SearchConfig,
+    rng: StdRng,
+}
+
+impl IsmctsStrategy {
+    pub fn new(config: SearchConfig) -> Self {
+        IsmctsStrategy {
+            config,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Returns a player making the same choices on the same positions, for a given seed.
+    pub fn seeded(config: SearchConfig, seed: Seed) -> Self {
+        IsmctsStrategy {
+            config,
+            rng: StdRng::from_seed(seed),
+        }
+    }
+}
+
+impl Default for IsmctsStrategy {
+    fn default() -> Self {
+        IsmctsStrategy::new(SearchConfig::default())
+    }
+}
+
+impl Strategy for IsmctsStrategy {
+    fn play(&mut self, observation: &Observation) -> Card {
+        if observation.legal_moves.size() == 1 {
+            return observation.legal_moves.get_card();
+        }
+        search(observation, &self.config, &mut self.rng)[0].card
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use thevalley_game::cards::{Deck, Rank, Suit};
+    use thevalley_game::rules::RuleSet;
+
+    fn config() -> SearchConfig {
+        SearchConfig {
+            iterations: 300,
+            ..SearchConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_takes_the_king() {
+        let heart_k = Card::new(Suit::Heart, Rank::RankK);
+        let heart_9 = Card::new(Suit::Heart, Rank::Rank9);
+        let heart_2 = Card::new(Suit::Heart, Rank::Rank2);
+        let mut hands = vec![Hand::new(); 2];
+        hands[0].add(heart_k).add(Card::new(Suit::Spade, Rank::Rank3));
+        hands[1].add(heart_9).add(heart_2);
+        let rules = RuleSet { hand_size: 2, ..RuleSet::default() };
+        let mut state = DealState::new(PlayerPos::P0, hands, Deck::empty(), rules);
+        state.play_card(PlayerPos::P0, heart_k).unwrap();
+
+        let observation = Observation::new(&state, PlayerPos::P1);
+        let scores = search(&observation, &config(), &mut StdRng::from_seed([1; 32]));
+        assert_eq!(scores.len(), 2);
+        assert_eq!(scores[0].card, heart_9);
+        assert!(scores[0].score > scores[1].score);
+        assert!(scores.iter().all(|s| (0.0..=1.0).contains(&s.score)));
+    }
+
+    #[test]
+    fn test_hidden_cards_are_not_used() {
+        // Both deals look the same to P0: they only differ by the hands of P1 and the stock
+        let rules = RuleSet::default();
+        let (hands, stock) = thevalley_game::deal_seeded_hands([8; 32], 2, &rules);
+        let state = DealState::new(PlayerPos::P0, hands.clone(), stock.clone(), rules.clone());
+        let mut other_hands = hands;
+        let mut other_stock = stock.cards().to_vec();
+        let hidden = other_hands[1].get_card();
+        other_hands[1].remove(hidden);
+        other_hands[1].add(other_stock[0]);
+        other_stock[0] = hidden;
+        let other = DealState::new(PlayerPos::P0, other_hands, Deck::from_cards(other_stock), rules);
+
+        let scores = search(&Observation::new(&state, PlayerPos::P0), &config(), &mut StdRng::from_seed([2; 32]));
+        let other_scores = search(&Observation::new(&other, PlayerPos::P0), &config(), &mut StdRng::from_seed([2; 32]));
+        assert_eq!(scores, other_scores);
+    }
+
+    #[test]
+    fn test_plays_legal_moves() {
+        let rules = RuleSet::default();
+        let (hands, stock) = thevalley_game::deal_seeded_hands([9; 32], 3, &rules);
+        let mut state = DealState::new(PlayerPos::P0, hands, stock, rules);
+        let mut strategy = IsmctsStrategy::seeded(SearchConfig { iterations: 20, ..config() }, [3; 32]);
+        while !state.is_over() {
+            let pos = state.next_player();
+            let card = strategy.play(&Observation::new(&state, pos));
+            assert!(state.play_card(pos, card).is_ok());
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Computer players for the valley card game.
+//!
+//! The players only use what they can see at the table: the hidden cards are
+//! sampled from the cards not seen yet, see [`sampling`].
+
+use thevalley_game::bot::{GreedyStrategy, RandomStrategy, SeededRandomStrategy, Strategy};
+use thevalley_game::Seed;
+
+pub mod analysis;
+pub mod arena;
+pub mod ismcts;
+pub mod sampling;
+pub mod solver;
+
+pub use analysis::{analyse, AnalysisConfig, GameAnalysis};
+pub use ismcts::{search, IsmctsStrategy, MoveScore, SearchConfig};
+pub use solver::{evaluate_moves, solve, MoveValue, Solver};
+
+/// Available strategies, from the simple ones of `thevalley_game::bot` to
+/// the search based ones of this crate.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum StrategyKind {
+    Random,
+    Greedy,
+    /// Information set Monte Carlo tree search
+    Ismcts,
+}
+
+impl StrategyKind {
+    pub const ALL: [StrategyKind; 3] = [StrategyKind::Random, StrategyKind::Greedy, StrategyKind::Ismcts];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StrategyKind::Random => "random",
+            StrategyKind::Greedy => "greedy",
+            StrategyKind::Ismcts => "ismcts",
+        }
+    }
+
+    /// Returns the strategy called `name`, see `name()`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        StrategyKind::ALL.iter().copied().find(|kind| kind.name() == name)
+    }
+}
+
+/// Returns a new player using the strategy `kind`.
+pub fn strategy(kind: StrategyKind) -> Box<dyn Strategy + Send> {
+    match kind {
+        StrategyKind::Random => Box::new(RandomStrategy),
+        StrategyKind::Greedy => Box::new(GreedyStrategy),
+        StrategyKind::Ismcts => Box::new(IsmctsStrategy::default()),
+    }
+}
+
+/// Returns a new player using the strategy `kind`, making the same choices
+/// on the same positions for a given seed.
+pub fn seeded_strategy(kind: StrategyKind, search: &SearchConfig, seed: Seed) -> Box<dyn Strategy + Send> {
+    match kind {
+        StrategyKind::Random => Box::new(SeededRandomStrategy::new(seed)),
+        StrategyKind::Greedy => Box::new(GreedyStrategy),
+        StrategyKind::Ismcts => Box::new(IsmctsStrategy::seeded(search.clone(), seed)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strategy_names() {
+        for kind in &StrategyKind::ALL {
+            assert_eq!(StrategyKind::from_name(kind.name()), Some(*kind));
+        }
+        assert_eq!(StrategyKind::from_name("smart"), None);
+    }
+}
@@ -0,0 +1,234 @@
+//! Sampling of the hidden cards, consistent with what a player has seen.
+//!
+//! A player knows its own hand and every card played so far. The other cards
+//! are either in the hands of its opponents or in the stock. The number of
+//! cards held by each opponent is public, and an opponent who did not follow
+//! suit has no card of that suit left, until the next draw.
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use thevalley_game::bot::Observation;
+use thevalley_game::cards::{Card, Deck, Hand};
+use thevalley_game::deal::DealState;
+
+/// Number of tries to deal the hidden cards while respecting the voids, before
+/// ignoring them.
+const MAX_TRIES: usize = 20;
+
+/// What a player knows of the hidden cards.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HiddenCards {
+    /// Cards neither in the player's hand nor played
+    unseen: Hand,
+    /// Number of cards held by each player
+    hand_sizes: Vec<usize>,
+    /// Cards each player is known not to hold
+    voids: Vec<Hand>,
+    stock_size: usize,
+}
+
+impl HiddenCards {
+    /// Gathers what `observation` tells about the cards the player can't see.
+    pub fn new(observation: &Observation) -> Self {
+        let nb_players = observation.nb_players();
+        let full = observation.rules.deck().cards().iter().fold(Hand::new(), |hand, card| hand | (*card).into());
+        let played = observation
+            .tricks
+            .iter()
+            .flat_map(|trick| trick.cards.iter().flatten())
+            .fold(Hand::new(), |hand, card| hand | (*card).into());
+        let unseen = full - observation.hand - played;
+
+        // Everybody holds as many cards at the start of a trick
+        let trick = observation.current_trick();
+        let start_size = observation.hand.size() + trick.card_played(observation.pos).map_or(0, |_| 1);
+        let hand_sizes: Vec<usize> = (0..nb_players)
+            .map(|n| start_size - trick.cards[n].map_or(0, |_| 1))
+            .collect();
+
+        // Each of the first tricks was followed by a draw, which may have
+        // filled a void
+        let in_play = played.size() + hand_sizes.iter().sum::<usize>() + observation.stock_size;
+        let initial_stock = in_play.saturating_sub(observation.rules.hand_size * nb_players);
+        let draws = initial_stock.saturating_sub(observation.stock_size) / nb_players;
+        let mut voids = vec![Hand::new(); nb_players];
+        for trick in observation.tricks.iter().skip(draws) {
+            let suit = match trick.suit() {
+                Some(suit) => suit,
+                None => continue,
+            };
+            for (n, card) in trick.cards.iter().enumerate() {
+                match card {
+                    Some(card) if !card.is_joker() && card.suit() != suit => {
+                        voids[n] = voids[n] | full.suit_cards(suit);
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        HiddenCards {
+            unseen,
+            hand_sizes,
+            voids,
+            stock_size: observation.stock_size,
+        }
+    }
+
+    /// Returns the cards the player can't see.
+    pub fn unseen(&self) -> Hand {
+        self.unseen
+    }
+
+    /// Returns the number of cards held by each player.
+    pub fn hand_sizes(&self) -> &[usize] {
+        &self.hand_sizes
+    }
+
+    /// Returns the cards `n`-th player is known not to hold.
+    pub fn voids(&self, n: usize) -> Hand {
+        self.voids[n]
+    }
+
+    /// Returns a deal where the unseen cards are randomly given to the other
+    /// players and to the stock, as seen by `observation.pos`.
+    ///
+    /// Voids are respected whenever possible. Unseen cards that don't fit
+    /// anywhere are left out: this only happens on positions built by hand,
+    /// where some cards were never dealt.
+    pub fn sample<R: Rng + ?Sized>(&self, observation: &Observation, rng: &mut R) -> DealState {
+        let (hands, stock) = (0..MAX_TRIES)
+            .find_map(|_| self.try_deal(observation, rng, true))
+            .or_else(|| self.try_deal(observation, rng, false))
+            .expect("hidden cards can always be dealt without voids");
+        DealState::from_position(hands, stock, observation.tricks.clone(), observation.rules.clone())
+    }
+
+    fn try_deal<R: Rng + ?Sized>(
+        &self,
+        observation: &Observation,
+        rng: &mut R,
+        with_voids: bool,
+    ) -> Option<(Vec<Hand>, Deck)> {
+        let me = observation.pos as usize;
+        let nb_players = self.hand_sizes.len();
+        let mut hands = vec![Hand::new(); nb_players];
+        hands[me] = observation.hand;
+        let mut stock = Vec::with_capacity(self.stock_size);
+
+        // Free places of each player, then of the stock, then for the cards out of play
+        let mut free: Vec<usize> = self.hand_sizes.clone();
+        free[me] = 0;
+        free.push(self.stock_size);
+        let placed: usize = free.iter().sum();
+        free.push(self.unseen.size().saturating_sub(placed));
+
+        let mut cards: Vec<Card> = self.unseen.list();
+        cards.shuffle(rng);
+        for card in cards {
+            let allowed = |n: usize| free[n] > 0 && !(with_voids && n < nb_players && self.voids[n].has(card));
+            let total: usize = (0..free.len()).filter(|n| allowed(*n)).map(|n| free[n]).sum();
+            if total == 0 {
+                return None;
+            }
+            let mut choice = rng.gen_range(0, total);
+            let n = (0..free.len())
+                .filter(|n| allowed(*n))
+                .find(|n| {
+                    if choice < free[*n] {
+                        true
+                    } else {
+                        choice -= free[*n];
+                        false
+                    }
+                })
+                .expect("choice is below the total of free places");
+            free[n] -= 1;
+            if n < nb_players {
+                hands[n].add(card);
+            } else if n == nb_players {
+                stock.push(card);
+            }
+        }
+        Some((hands, Deck::from_cards(stock)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use thevalley_game::cards::{Rank, Suit};
+    use thevalley_game::pos::PlayerPos;
+    use thevalley_game::rules::RuleSet;
+
+    fn play_n(nb_players: usize, seed: u8, n: usize) -> DealState {
+        let rules = RuleSet::default();
+        let (hands, stock) = thevalley_game::deal_seeded_hands([seed; 32], nb_players, &rules);
+        let mut state = DealState::new(PlayerPos::P0, hands, stock, rules);
+        for _ in 0..n {
+            let pos = state.next_player();
+            let card = state.legal_moves(pos).max_by_strength(|c| c.id() as i32).unwrap();
+            state.play_card(pos, card).unwrap();
+        }
+        state
+    }
+
+    #[test]
+    fn test_hidden_cards() {
+        let state = play_n(3, 1, 4);
+        let pos = state.next_player();
+        let observation = Observation::new(&state, pos);
+        let hidden = HiddenCards::new(&observation);
+
+        let sizes: Vec<usize> = state.hands().iter().map(|hand| hand.size()).collect();
+        assert_eq!(hidden.hand_sizes(), &sizes[..]);
+        let others = state.hands().iter().enumerate().filter(|(n, _)| *n != pos as usize);
+        let expected = others.fold(Hand::new(), |unseen, (_, hand)| unseen | *hand);
+        assert_eq!(hidden.unseen().size(), expected.size() + state.stock_size());
+        assert_eq!(hidden.unseen() | expected, hidden.unseen());
+
+        let mut rng = StdRng::from_seed([3; 32]);
+        for _ in 0..20 {
+            let sample = hidden.sample(&observation, &mut rng);
+            assert_eq!(sample.hands()[pos as usize], state.hands()[pos as usize]);
+            assert_eq!(sample.next_player(), pos);
+            assert_eq!(sample.stock_size(), state.stock_size());
+            assert_eq!(sample.points(), state.points());
+            assert_eq!(sample.tricks(), state.tricks());
+            let sizes: Vec<usize> = sample.hands().iter().map(|hand| hand.size()).collect();
+            assert_eq!(hidden.hand_sizes(), &sizes[..]);
+        }
+    }
+
+    #[test]
+    fn test_voids() {
+        // No stock: P1 shows a void in hearts
+        let heart_5 = Card::new(Suit::Heart, Rank::Rank5);
+        let spade_4 = Card::new(Suit::Spade, Rank::Rank4);
+        let mut hands = vec![Hand::new(); 3];
+        hands[0].add(heart_5).add(Card::new(Suit::Club, Rank::Rank2));
+        hands[1].add(spade_4).add(Card::new(Suit::Diamond, Rank::Rank9));
+        hands[2].add(Card::new(Suit::Heart, Rank::Rank2)).add(Card::new(Suit::Heart, Rank::RankK));
+        let rules = RuleSet { hand_size: 2, ..RuleSet::default() };
+        let mut state = DealState::new(PlayerPos::P0, hands, Deck::empty(), rules);
+        state.play_card(PlayerPos::P0, heart_5).unwrap();
+        state.play_card(PlayerPos::P1, spade_4).unwrap();
+
+        let observation = Observation::new(&state, PlayerPos::P2);
+        let hidden = HiddenCards::new(&observation);
+        assert!(hidden.voids(1).has(Card::new(Suit::Heart, Rank::RankQ)));
+        assert!(!hidden.voids(1).has(Card::new(Suit::Club, Rank::RankQ)));
+        assert!(hidden.voids(0).is_empty());
+
+        let mut rng = StdRng::from_seed([4; 32]);
+        for _ in 0..20 {
+            let sample = hidden.sample(&observation, &mut rng);
+            assert!(!sample.hands()[1].has_any(Suit::Heart));
+            assert_eq!(sample.hands()[1].size(), 1);
+            assert_eq!(sample.hands()[0].size(), 1);
+        }
+    }
+}
@@ -0,0 +1,357 @@
+//! Double dummy solver: best play when every card is known.
+//!
+//! The player to move tries to win as many points as possible while the other
+//! players try to keep them low. With two players this is the actual optimal
+//! play, since the points of a deal add up to a constant. The tree of a full
+//! deal is large: the solver is meant for the last tricks, or to be given a
+//! node limit.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+
+use thevalley_game::cards::{Card, Hand};
+use thevalley_game::deal::DealState;
+use thevalley_game::pos::PlayerPos;
+
+/// Error returned when a position can't be solved.
+#[derive(PartialEq, Debug)]
+pub enum SolveError {
+    /// The deal is over, there is no move to find
+    DealOver,
+    /// The search visited more positions than allowed
+    NodeLimit,
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SolveError::DealOver => write!(f, "the deal is over"),
+            SolveError::NodeLimit => write!(f, "too many positions to search"),
+        }
+    }
+}
+
+/// Exact evaluation of a move.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveValue {
+    pub card: Card,
+    /// Points of the player to move at the end of the deal, after this move
+    pub score: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+/// Stored result of a search, as the points still to be won.
+struct Entry {
+    value: f32,
+    bound: Bound,
+}
+
+/// Position at the start of a trick, from the point of view of a player.
+///
+/// The cards left in the stock are part of it, in their order: the table
+/// may be shared by deals with the same hands and another stock.
+#[derive(PartialEq, Eq, Hash)]
+struct Key {
+    me: PlayerPos,
+    leader: PlayerPos,
+    first_trick: bool,
+    stock: Vec<Card>,
+    hands: Vec<Hand>,
+}
+
+impl Key {
+    /// Returns the key of `state`, if no card was played in the current trick.
+    fn new(state: &DealState, me: PlayerPos) -> Option<Self> {
+        if state.current_trick().cards.iter().any(Option::is_some) {
+            return None;
+        }
+        Some(Key {
+            me,
+            leader: state.next_player(),
+            first_trick: state.tricks().len() == 1,
+            stock: state.stock().cards().to_vec(),
+            hands: state.hands().to_vec(),
+        })
+    }
+}
+
+/// Alpha-beta search with a transposition table.
+///
+/// The table is kept between calls, so solving the successive positions of a
+/// deal, or the samples of a search, gets cheaper.
+#[derive(Default)]
+pub struct Solver {
+    table: HashMap<Key, Entry>,
+    node_limit: Option<u64>,
+    nodes: u64,
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Solver::default()
+    }
+
+    /// Returns a solver giving up after visiting `limit` positions in a call.
+    pub fn with_node_limit(limit: u64) -> Self {
+        Solver {
+            node_limit: Some(limit),
+            ..Solver::default()
+        }
+    }
+
+    /// Returns the number of positions visited by the last call.
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Returns the final points of the player to move with the best play of
+    /// everyone, and the card to play to get them.
+    pub fn solve(&mut self, state: &DealState) -> Result<(f32, Card), SolveError> {
+        if state.is_over() {
+            return Err(SolveError::DealOver);
+        }
+        self.nodes = 0;
+        let me = state.next_player();
+        let mut alpha = f32::NEG_INFINITY;
+        let mut best: Option<(f32, Card)> = None;
+        for card in ordered_moves(state, me) {
+            let score = self.value(&after(state, card), me, alpha, f32::INFINITY)?;
+            if best.is_none() || best.unwrap().0 < score {
+                best = Some((score, card));
+                alpha = score;
+            }
+        }
+        Ok(best.expect("a player always has a legal move"))
+    }
+
+    /// Returns the exact value of every legal move of the player to move,
+    /// from the best one.
+    pub fn evaluate_moves(&mut self, state: &DealState) -> Result<Vec<MoveValue>, SolveError> {
+        if state.is_over() {
+            return Err(SolveError::DealOver);
+        }
+        self.nodes = 0;
+        let me = state.next_player();
+        let mut values = Vec::new();
+        for card in ordered_moves(state, me) {
+            let score = self.value(&after(state, card), me, f32::NEG_INFINITY, f32::INFINITY)?;
+            values.push(MoveValue { card, score });
+        }
+        values.sort_by(|a, b| b.score.partial_cmp(&a.score).expect("points are numbers"));
+        Ok(values)
+    }
+
+    /// Returns the final points of `me`, or a bound of them outside of
+    /// `alpha..beta`.
+    fn value(&mut self, state: &DealState, me: PlayerPos, mut alpha: f32, mut beta: f32) -> Result<f32, SolveError> {
+        let won = state.points()[me as usize];
+        if state.is_over() {
+            return Ok(won);
+        }
+        self.nodes += 1;
+        if matches!(self.node_limit, Some(limit) if self.nodes > limit) {
+            return Err(SolveError::NodeLimit);
+        }
+
+        let key = Key::new(state, me);
+        if let Some(entry) = key.as_ref().and_then(|key| self.table.get(key)) {
+            let value = won + entry.value;
+            match entry.bound {
+                Bound::Exact => return Ok(value),
+                Bound::Lower => alpha = alpha.max(value),
+                Bound::Upper => beta = beta.min(value),
+            }
+            if alpha >= beta {
+                return Ok(value);
+            }
+        }
+
+        let (first_alpha, first_beta) = (alpha, beta);
+        let pos = state.next_player();
+        let maximize = pos == me;
+        let mut best = if maximize { f32::NEG_INFINITY } else { f32::INFINITY };
+        for card in ordered_moves(state, pos) {
+            let value = self.value(&after(state, card), me, alpha, beta)?;
+            if maximize {
+                best = best.max(value);
+                alpha = alpha.max(best);
+            } else {
+                best = best.min(value);
+                beta = beta.min(best);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        if let Some(key) = key {
+            let bound = if best <= first_alpha {
+                Bound::Upper
+            } else if best >= first_beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+            self.table.insert(key, Entry { value: best - won, bound });
+        }
+        Ok(best)
+    }
+}
+
+/// Returns the legal moves of `pos`, strongest first: they are the most
+/// likely to end the search early.
+fn ordered_moves(state: &DealState, pos: PlayerPos) -> Vec<Card> {
+    let rules = state.rules();
+    let mut moves = state.legal_moves(pos).list();
+    moves.sort_by_key(|card| Reverse(rules.strength(*card)));
+    moves
+}
+
+fn after(state: &DealState, card: Card) -> DealState {
+    let mut state = state.clone();
+    let pos = state.next_player();
+    state.play_card(pos, card).expect("solver move is legal");
+    state
+}
+
+/// Returns the final points of the player to move with the best play of
+/// everyone, and the card to play to get them.
+///
+/// # Panics
+///
+/// Panics if the deal is over.
+pub fn solve(state: &DealState) -> (f32, Card) {
+    Solver::new().solve(state).expect("the deal is not over")
+}
+
+/// Returns the exact value of every legal move of the player to move, from
+/// the best one.
+///
+/// # Panics
+///
+/// Panics if the deal is over.
+pub fn evaluate_moves(state: &DealState) -> Vec<MoveValue> {
+    Solver::new().evaluate_moves(state).expect("the deal is not over")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use thevalley_game::cards::{Deck, Rank, Suit};
+    use thevalley_game::rules::RuleSet;
+
+    /// Plays the strongest legal card until `pos` holds `cards` cards.
+    fn endgame(nb_players: usize, seed: u8, cards: usize) -> DealState {
+        let rules = RuleSet::default();
+        let (hands, stock) = thevalley_game::deal_seeded_hands([seed; 32], nb_players, &rules);
+        let mut state = DealState::new(PlayerPos::P0, hands, stock, rules);
+        while state.stock_size() > 0 || state.hands()[state.next_player() as usize].size() > cards {
+            let pos = state.next_player();
+            let card = ordered_moves(&state, pos)[0];
+            state.play_card(pos, card).unwrap();
+        }
+        state
+    }
+
+    /// Minimax without any pruning.
+    fn brute_force(state: &DealState, me: PlayerPos) -> f32 {
+        if state.is_over() {
+            return state.points()[me as usize];
+        }
+        let values = ordered_moves(state, state.next_player())
+            .into_iter()
+            .map(|card| brute_force(&after(state, card), me));
+        if state.next_player() == me {
+            values.fold(f32::NEG_INFINITY, f32::max)
+        } else {
+            values.fold(f32::INFINITY, f32::min)
+        }
+    }
+
+    #[test]
+    fn test_takes_the_king() {
+        let heart_k = Card::new(Suit::Heart, Rank::RankK);
+        let heart_9 = Card::new(Suit::Heart, Rank::Rank9);
+        let heart_2 = Card::new(Suit::Heart, Rank::Rank2);
+        let mut hands = vec![Hand::new(); 2];
+        hands[0].add(heart_k).add(Card::new(Suit::Spade, Rank::Rank3));
+        hands[1].add(heart_9).add(heart_2);
+        let rules = RuleSet { hand_size: 2, ..RuleSet::default() };
+        let mut state = DealState::new(PlayerPos::P0, hands, Deck::empty(), rules);
+        state.play_card(PlayerPos::P0, heart_k).unwrap();
+
+        // The 9 wins the king and the last trick
+        assert_eq!(solve(&state), (5.0, heart_9));
+        let values = evaluate_moves(&state);
+        assert_eq!(
+            values,
+            vec![MoveValue { card: heart_9, score: 5.0 }, MoveValue { card: heart_2, score: 0.0 }]
+        );
+
+        let mut over = after(&after(&state, heart_9), heart_2);
+        over.play_card(PlayerPos::P0, Card::new(Suit::Spade, Rank::Rank3)).unwrap();
+        assert_eq!(Solver::new().solve(&over), Err(SolveError::DealOver));
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        for (nb_players, seed) in &[(2, 1), (2, 2), (3, 3), (3, 4)] {
+            let state = endgame(*nb_players, *seed, 4);
+            let me = state.next_player();
+            let expected = brute_force(&state, me);
+            let mut solver = Solver::new();
+            let (score, card) = solver.solve(&state).unwrap();
+            assert_eq!(score, expected);
+            assert_eq!(brute_force(&after(&state, card), me), expected);
+
+            let values = solver.evaluate_moves(&state).unwrap();
+            assert_eq!(values.len(), state.legal_moves(me).size());
+            assert_eq!(values[0].score, expected);
+            for value in values {
+                assert_eq!(value.score, brute_force(&after(&state, value.card), me));
+            }
+        }
+    }
+
+    #[test]
+    fn test_stock_changes() {
+        // The two deals reach the same hands after the first trick, only the
+        // order of the last cards of the stock differs
+        let heart_k = Card::new(Suit::Heart, Rank::RankK);
+        let heart_9 = Card::new(Suit::Heart, Rank::Rank9);
+        let heart_3 = Card::new(Suit::Heart, Rank::Rank3);
+        let heart_2 = Card::new(Suit::Heart, Rank::Rank2);
+        let spade_3 = Card::new(Suit::Spade, Rank::Rank3);
+        let spade_2 = Card::new(Suit::Spade, Rank::Rank2);
+        let mut hands = vec![Hand::new(); 2];
+        hands[0].add(heart_2);
+        hands[1].add(spade_2);
+        let rules = RuleSet { hand_size: 1, ..RuleSet::default() };
+        let deal = |stock: Vec<Card>| {
+            DealState::new(PlayerPos::P0, hands.clone(), Deck::from_cards(stock), rules.clone())
+        };
+        let first = deal(vec![heart_9, heart_k, heart_3, spade_3]);
+        let second = deal(vec![heart_k, heart_9, heart_3, spade_3]);
+
+        let mut solver = Solver::new();
+        for state in &[first, second] {
+            let me = state.next_player();
+            assert_eq!(solver.solve(state).unwrap().0, brute_force(state, me));
+        }
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let state = endgame(2, 5, 6);
+        let mut solver = Solver::with_node_limit(10);
+        assert_eq!(solver.solve(&state), Err(SolveError::NodeLimit));
+        assert!(Solver::new().solve(&state).is_ok());
+    }
+}
@@ -5,6 +5,7 @@ use tr::tr;
 use yew::{html, Component, ComponentLink, Html, Properties, ShouldRender};
 
 use crate::protocol::{GameStateSnapshot, PlayerRole, GamePlayerState, Turn};
+use crate::utils::format_clock;
 
 #[derive(Clone, Properties)]
 pub struct Props {
@@ -43,11 +44,15 @@ impl Component for PlayerList {
             <section class="players">
                 {
                     for self.players.iter().map(|state| {
-                        let card_played = self.game_state.deal.last_trick.card_played(state.pos);
-                        let is_my_turn = self.game_state.get_playing_pos() == Some(state.pos);
+                        let card_played = state.pos.and_then(|pos| self.game_state.deal.last_trick.card_played(pos));
+                        let is_my_turn = state.is_seated() && self.game_state.get_playing_pos() == state.pos;
 
-                        let scores = self.game_state.scores.last().unwrap_or(&[0.0;2]);
-                        let my_points= scores[state.pos.to_n()];
+                        let scores = self.game_state.scores.last().map_or(&[][..], |scores| &scores[..]);
+                        let my_points = state.pos.and_then(|pos| scores.get(pos.to_n())).copied().unwrap_or(0.0);
+                        // Only sent to the spectators of an open table
+                        let open_hand = self.game_state.deal.hands.as_ref()
+                            .and_then(|hands| state.pos.and_then(|pos| hands.get(pos.to_n())));
+                        let clock = state.pos.and_then(|pos| self.game_state.clocks.as_ref()?.get(pos.to_n()).copied());
                         let mut player_classes = vec!["player"];
                         if is_my_turn {
                             player_classes.push("current-player");
@@ -83,11 +88,18 @@ impl Component for PlayerList {
                                 html!{}
                             }
                         }
+                        {
+                            if let Some(clock) = clock {
+                                html! { <span class="clock">{ format_clock(clock) }</span> }
+                            } else {
+                                html!{}
+                            }
+                        }
                         </div>
                         <div class="action">
                         {
                             if let Some(card) = card_played {
-                                let style =format!("cursor: default; --bg-image: url('cards/{}-{}.svg')", &card.rank().to_string(), &card.suit().to_safe_string());
+                                let style =format!("cursor: default; --bg-image: url('cards/{}.svg')", card.to_safe_string());
                                 html! {
                                     <div class="card" style={style}></div>
                                 }
@@ -96,6 +108,20 @@ impl Component for PlayerList {
                             }
                         }
                         </div>
+                        {
+                            if let Some(hand) = open_hand {
+                                html! {
+                                    <div class="open-hand">
+                                    { for hand.list().iter().map(|card| {
+                                        let style = format!("cursor: default; --bg-image: url('cards/{}.svg')", card.to_safe_string());
+                                        html! { <div class="card" style={style}></div> }
+                                    }) }
+                                    </div>
+                                }
+                            } else {
+                                html!{}
+                            }
+                        }
                         </div>
                     }})
                 }
@@ -33,7 +33,8 @@ impl Component for Scores {
 
 
     fn view(&self) -> Html {
-        let mut total = vec![0.0,0.0,0.0,0.0,0.0];
+        let nb_players = self.scores.iter().map(|score| score.len()).max().unwrap_or(0);
+        let mut total = vec![0.0; nb_players];
         for score in self.scores.iter() {
             for (idx, points) in score.iter().enumerate() {
                 total[idx] = total[idx] + points;
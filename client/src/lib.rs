@@ -58,6 +58,8 @@ pub struct App {
     state: AppState,
     player_info: Option<PlayerInfo>,
     game_info: Option<GameInfo>,
+    /// Game stored before joining the current one
+    previous_game: Option<uuid::Uuid>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -123,6 +125,7 @@ impl Component for App {
             link,
             api,
             state: AppState::Start,
+            previous_game: game_info.as_ref().map(|info| info.game_id),
             player_info,
             game_info,
         }
@@ -137,6 +140,10 @@ impl Component for App {
             }
             Msg::GameJoined(game_info) => {
                 self.state = AppState::InGame;
+                if self.previous_game == Some(game_info.game_id) {
+                    // Back in the same game, nothing to resume
+                    self.previous_game = None;
+                }
                 self.storage.store(KEY_GAME, Json(&game_info));
                 self.game_info = Some(game_info);
             }
@@ -181,6 +188,7 @@ impl Component for App {
                     <GamePage
                         player_info=self.player_info.as_ref().unwrap().clone(),
                         game_info=self.game_info.as_ref().unwrap().clone(),
+                        previous_game=self.previous_game,
                          />
                 }
             }}
@@ -6,3 +6,9 @@ pub fn format_join_code(code: &str) -> String {
         code
     }
 }
+
+/// Formats milliseconds left on a clock as minutes and seconds.
+pub fn format_clock(ms: u64) -> String {
+    let seconds = (ms + 999) / 1000;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
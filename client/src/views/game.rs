@@ -6,7 +6,7 @@ use uuid::Uuid;
 use yew::agent::Bridged;
 use yew::services::{IntervalService, Task};
 use yew::{
-    html, Bridge, Component, ComponentLink, Html, Properties,
+    html, Bridge, Component, ComponentLink, Html, InputData, Properties,
     ShouldRender,
 };
 use tr::tr;
@@ -20,29 +20,46 @@ use crate::protocol::{
     Command, GamePlayerState, GameStateSnapshot, Message, PlayerAction,
     GamePlayCommand,
     PlayCommand,
+    SetOptionsCommand,
+    AddBotCommand,
+    RestoreCommand,
+    GameOptions,
+    MatchEnd,
+    TimeControl,
+    TimeoutAction,
     Turn,
     PlayEvent,
+    GameMessage,
+    ErrorCode,
+    ProtocolError,
 };
-use thevalley_game::cards;
-use crate::utils::format_join_code;
+use thevalley_ai::StrategyKind;
+use thevalley_game::{cards, format_seed, parse_seed, MIN_PLAYERS, MAX_PLAYERS};
+use crate::utils::{format_clock, format_join_code};
 use crate::sound_player::SoundPlayer;
 
 #[derive(Clone, Properties)]
 pub struct Props {
     pub player_info: PlayerInfo,
     pub game_info: GameInfo,
+    /// Last game of the player before this one, which may be resumed
+    pub previous_game: Option<Uuid>,
 }
 
 pub struct GamePage {
     #[allow(dead_code)]
     keepalive_job: Box<dyn Task>,
+    #[allow(dead_code)]
+    clock_job: Box<dyn Task>,
     link: ComponentLink<GamePage>,
     api: Box<dyn Bridge<Api>>,
     game_info: GameInfo,
     player_info: PlayerInfo,
+    previous_game: Option<Uuid>,
     game_state: Rc<GameStateSnapshot>,
     chat_log: Vector<Rc<ChatLine>>,
     hand: cards::Hand,
+    seed_input: String,
     is_waiting: bool,
     sound_player: SoundPlayer,
     error: Option<String>,
@@ -55,6 +72,16 @@ pub enum Msg {
     Continue,
     CloseError,
     Play(cards::Card),
+    SetMatchEnd(MatchEnd),
+    SetNbPlayers(usize),
+    SetOpenTable(bool),
+    SetTimeControl(TimeControl),
+    SetTimeoutAction(TimeoutAction),
+    Tick,
+    SetSeedInput(String),
+    SetSeed,
+    AddBot(StrategyKind),
+    Restore,
     SetChatLine(String),
     AddToHand(cards::Card),
     ServerMessage(Message),
@@ -77,6 +104,13 @@ impl GamePage {
         }
     }
 
+    fn show_error(&mut self, error: &ProtocolError) {
+        self.is_waiting = false;
+        self.error = Some(error_text(error));
+        self.sound_player.play("error".into());
+        log!("error from server {:?}", error);
+    }
+
     pub fn my_state(&self) -> &GamePlayerState {
         self.game_state
             .players
@@ -97,6 +131,12 @@ impl Component for GamePage {
             link.callback(|_| Msg::Ping).into()
             );
 
+        // Counts down the clock of the current player between two updates
+        let clock = IntervalService::spawn(
+            Duration::from_secs(1),
+            link.callback(|_| Msg::Tick).into()
+            );
+
         let on_server_message = link.callback(Msg::ServerMessage);
         let api = Api::bridge(on_server_message);
         let sound_paths = vec![
@@ -107,9 +147,11 @@ impl Component for GamePage {
 
         GamePage {
             keepalive_job: Box::new(keepalive),
+            clock_job: Box::new(clock),
             link,
             api,
             game_info: props.game_info,
+            previous_game: props.previous_game,
             chat_log: Vector::unit(Rc::new(ChatLine {
                 nickname: props.player_info.nickname.clone(),
                 data: ChatLineData::Connected,
@@ -117,6 +159,7 @@ impl Component for GamePage {
             game_state: Rc::new(GameStateSnapshot::default()),
             player_info: props.player_info,
             hand: cards::Hand::new(),
+            seed_input: String::new(),
             is_waiting: false,
             sound_player: SoundPlayer::new(sound_paths),
             error: None,
@@ -134,18 +177,37 @@ impl Component for GamePage {
                     self.sound_player.play("chat".into());
                     self.add_chat_message(msg.player_id, ChatLineData::Text(msg.text));
                 }
-                Message::PlayEvent(evt) => {
-                    self.sound_player.play("card".into());
-                    let PlayEvent::Play(uuid, card) = evt;
-                    self.add_chat_message(uuid, ChatLineData::Text(format!("play: {}", card.to_string())));
+                Message::PlayEvent(GameMessage::Event(evt)) => {
                     log!("play event {:?}", evt);
+                    match evt.event {
+                        PlayEvent::Play(uuid, card) => {
+                            if uuid == self.player_info.id {
+                                self.is_waiting = false;
+                            }
+                            self.sound_player.play("card".into());
+                            self.add_chat_message(uuid, ChatLineData::Text(tr!("played {0}", card.to_string())));
+                        }
+                        PlayEvent::TrickWon { winner, .. } => {
+                            if let Some(state) = self.game_state.players.iter().find(|p| p.pos == Some(winner)) {
+                                let uuid = state.player.id;
+                                self.add_chat_message(uuid, ChatLineData::Text(tr!("won the trick")));
+                            }
+                        }
+                        PlayEvent::PlayerTimedOut(uuid) => {
+                            self.add_chat_message(uuid, ChatLineData::Text(tr!("ran out of time")));
+                        }
+                        _ => {}
+                    }
+                    let game_state = Rc::make_mut(&mut self.game_state);
+                    if game_state.apply_event(self.player_info.id, &evt) {
+                        self.hand = game_state.deal.hand.unwrap_or_default();
+                    } else {
+                        // Some events were missed, after a reconnection
+                        self.api.send(Command::GamePlay(GamePlayCommand::Resync));
+                    }
                 }
-                Message::Error(e) => {
-                    self.is_waiting = false;
-                    self.error = Some(e.message().into());
-                    self.sound_player.play("error".into());
-                    log!("error from server {:?}", e);
-                }
+                Message::PlayEvent(GameMessage::Error(e)) => self.show_error(&e),
+                Message::Error(e) => self.show_error(&ProtocolError::from(&e)),
                 Message::PlayerConnected(state) => {
                     let player_id = state.player.id;
                     let game_state = Rc::make_mut(&mut self.game_state);
@@ -160,7 +222,7 @@ impl Component for GamePage {
                 Message::GameStateSnapshot(snapshot) => {
                     self.is_waiting = false;
                     self.game_state = Rc::new(snapshot);
-                    self.hand = self.game_state.deal.hand;
+                    self.hand = self.game_state.deal.hand.unwrap_or_default();
                 }
                 _ => {}
             },
@@ -174,13 +236,14 @@ impl Component for GamePage {
             Msg::CloseError => {
                 self.error = None;
             }
-            Msg::Continue => {
+            Msg::Continue | Msg::MarkReady => {
                 self.is_waiting = true;
-                self.api.send(Command::Continue);
+                self.api.send(Command::GamePlay(GamePlayCommand::Ready));
             }
-            Msg::MarkReady => {
-                self.is_waiting = true;
-                self.api.send(Command::MarkReady);
+            Msg::Restore => {
+                if let Some(game_id) = self.previous_game.take() {
+                    self.api.send(Command::GamePlay(GamePlayCommand::Restore(RestoreCommand { game_id })));
+                }
             }
             Msg::Disconnect => {
                 self.api.send(Command::LeaveGame);
@@ -192,6 +255,52 @@ impl Component for GamePage {
                 self.is_waiting = true;
                 self.api.send(Command::GamePlay(GamePlayCommand::Play(PlayCommand { card })));
             }
+            Msg::SetMatchEnd(match_end) => {
+                let options = GameOptions { match_end, ..self.game_state.options.clone() };
+                self.api.send(Command::GamePlay(GamePlayCommand::SetOptions(SetOptionsCommand { options })));
+            }
+            Msg::SetNbPlayers(nb_players) => {
+                let options = GameOptions { nb_players, ..self.game_state.options.clone() };
+                self.api.send(Command::GamePlay(GamePlayCommand::SetOptions(SetOptionsCommand { options })));
+            }
+            Msg::SetOpenTable(open_table) => {
+                let options = GameOptions { open_table, ..self.game_state.options.clone() };
+                self.api.send(Command::GamePlay(GamePlayCommand::SetOptions(SetOptionsCommand { options })));
+            }
+            Msg::SetTimeControl(time_control) => {
+                let options = GameOptions { time_control, ..self.game_state.options.clone() };
+                self.api.send(Command::GamePlay(GamePlayCommand::SetOptions(SetOptionsCommand { options })));
+            }
+            Msg::SetTimeoutAction(timeout_action) => {
+                let options = GameOptions { timeout_action, ..self.game_state.options.clone() };
+                self.api.send(Command::GamePlay(GamePlayCommand::SetOptions(SetOptionsCommand { options })));
+            }
+            Msg::Tick => {
+                let pos = match self.game_state.get_playing_pos() {
+                    Some(pos) if self.game_state.clocks.is_some() => pos,
+                    _ => return false,
+                };
+                let game_state = Rc::make_mut(&mut self.game_state);
+                if let Some(clock) = game_state.clocks.as_mut().and_then(|clocks| clocks.get_mut(pos.to_n())) {
+                    *clock = clock.saturating_sub(1000);
+                }
+            }
+            Msg::SetSeedInput(seed_input) => {
+                self.seed_input = seed_input;
+            }
+            Msg::SetSeed => {
+                if let Some(seed) = parse_seed(&self.seed_input) {
+                    let options = GameOptions { seed: Some(seed), ..self.game_state.options.clone() };
+                    self.api.send(Command::GamePlay(GamePlayCommand::SetOptions(SetOptionsCommand { options })));
+                } else {
+                    self.error = Some(tr!("invalid seed"));
+                }
+            }
+            Msg::AddBot(strategy) => {
+                self.api.send(Command::GamePlay(GamePlayCommand::AddBot(AddBotCommand {
+                    strategy: strategy.name().to_string(),
+                })));
+            }
         }
         true
     }
@@ -202,25 +311,28 @@ impl Component for GamePage {
         }
 
         let my_state = self.my_state();
-        let card_played = self.game_state.deal.last_trick.card_played(my_state.pos);
+        let is_seated = my_state.is_seated();
+        let card_played = my_state.pos.and_then(|pos| self.game_state.deal.last_trick.card_played(pos));
         let player_action = my_state.get_turn_player_action(self.game_state.turn);
 
-        // display players in order of playing starting from the current player
+        // display players in order of playing starting from the current player,
+        // spectators see the seats in order
         let mut others_before = vec![];
         let mut others = vec![];
-        let mypos = my_state.pos.to_n();
+        let mypos = my_state.pos.map(|pos| pos.to_n());
 
-        // let mut positioned = Vec::from_iter(self.game_state.players.clone());
-        // positioned.sort_by(|a, b| a.pos.to_n().cmp(&b.pos.to_n()));
-        // for pstate in positioned.iter() {
-        for pstate in self.game_state.players.iter() {
-            let pos = pstate.pos.to_n();
+        for pstate in self.game_state.seated_players() {
+            let pos = pstate.pos.map(|pos| pos.to_n());
             if pos < mypos {
                 others_before.push(pstate.clone());
-            } else if mypos < pos{
+            } else if pos != mypos {
                others.push(pstate.clone());
             }
         }
+        let spectators: Vec<String> = self.game_state.players.iter()
+            .filter(|pstate| !pstate.is_seated())
+            .map(|pstate| pstate.player.nickname.clone())
+            .collect();
 
         // log!("others: {:?} others_before: {:?}", others, others_before);
         others.append(&mut others_before);
@@ -230,7 +342,7 @@ impl Component for GamePage {
             game_classes.push("waiting");
         }
 
-        let is_my_turn = self.game_state.get_playing_pos() == Some(self.my_state().pos);
+        let is_my_turn = is_seated && self.game_state.get_playing_pos() == my_state.pos;
         // let is_my_turn = self.game_state.turn.has_player_pos() && self.game_state.deal.current == self.my_state().pos;
         let mut actions_classes = vec!["actions"];
         if is_my_turn {
@@ -239,7 +351,7 @@ impl Component for GamePage {
 
         let message_content: Option<Html> = match self.game_state.turn {
                Turn::Intertrick => 
-                   if !self.my_state().ready  { 
+                   if is_seated && !self.my_state().ready  { 
                        let winner_pos = self.game_state.deal.last_trick.winner;
                        let winner_name = self.game_state.pos_player_name(winner_pos);
                        Some(html! { 
@@ -251,9 +363,9 @@ impl Component for GamePage {
                        })
                    } else { None },
                Turn::Interdeal => 
-                   if !self.my_state().ready  { 
+                   if is_seated && !self.my_state().ready  { 
                        let scores: Vec<Vec<f32>> = self.game_state.scores.iter().map(|score| score.to_vec()).collect();
-                       let players: Vec<String> = self.game_state.players.iter().map(|pl| pl.player.nickname.clone()).collect();
+                       let players: Vec<String> = self.game_state.seated_players().iter().map(|pl| pl.player.nickname.clone()).collect();
 
                        let taker_won = false;
                        let diff_abs = 0;
@@ -266,8 +378,33 @@ impl Component for GamePage {
                        Some(html! {
                      <div>
                         <Scores players=players scores=scores />
+                        { for self.game_state.deal_seeds.last().map(|seed| html! {
+                            <div class="seed">{ tr!("seed of the deal: {0}", format_seed(seed)) }</div>
+                        }) }
                      </div>
                    })} else { None },
+               Turn::Endgame => {
+                   let scores: Vec<Vec<f32>> = self.game_state.scores.iter().map(|score| score.to_vec()).collect();
+                   let players: Vec<String> = self.game_state.seated_players().iter().map(|pl| pl.player.nickname.clone()).collect();
+                   let result = match self.game_state.winner {
+                       Some(pos) => tr!("{0} wins the match", self.game_state.pos_player_name(pos)),
+                       None => tr!("The match is a draw"),
+                   };
+                   Some(html! {
+                     <div>
+                        <div class="results"><strong>{ result }</strong></div>
+                        <Scores players=players scores=scores />
+                        { for self.game_state.seed.iter().map(|seed| html! {
+                            <div class="seed">{ tr!("seed of the game: {0}", format_seed(seed)) }</div>
+                        }) }
+                        { for self.game_state.record.iter().map(|record| html! {
+                            <div class="record">
+                                <div>{ tr!("game record") }</div>
+                                <textarea readonly=true rows="12" value=record.to_text() />
+                            </div>
+                        }) }
+                     </div>
+                   })},
               _ => None
         };
 
@@ -284,25 +421,28 @@ impl Component for GamePage {
     <div class=game_classes>
       <header>
         <p class="turn-info">{turn_info}</p>
+        { if self.game_state.turn.has_player_pos() {
+            html! { <p class="stock-info">{ tr!("{0} cards in stock", self.game_state.deal.stock_size) }</p> }
+        } else { html! {} }}
+        { if let Some(clock) = my_state.pos.and_then(|pos| self.game_state.clocks.as_ref()?.get(pos.to_n())) {
+            html! { <p class="clock-info">{ tr!("time left: {0}", format_clock(*clock)) }</p> }
+        } else { html! {} }}
+        { if !is_seated {
+            html! { <p class="spectator-info">{ tr!("You are watching the game") }</p> }
+        } else { html! {} }}
+        { if !spectators.is_empty() {
+            html! { <p class="spectators">{ tr!("spectators: {0}", spectators.join(", ")) }</p> }
+        } else { html! {} }}
       </header>
 
       <PlayerList game_state=self.game_state.clone() players=others/>
 
         { if let Some(error) = &self.error  { 
-            let error_str = match error.as_str() {
-            "play: invalid turn order" => tr!("invalid turn order"),
-            "play: you can only play cards you have" => tr!("you can only play cards you have" ),
-            "play: wrong suit played" => tr!("wrong suit played" ),
-            "play: you must use trumps" => tr!("you must use trumps" ),
-            "play: too weak trump played" => tr!("too weak trump played" ),
-            "play: no trick has been played yet" => tr!("no trick has been played yet" ),
-            _ => error.to_string()
-            };
             html! {
           <div class="notify-wrapper">
             <div class="error notify">
                 <div>
-                { error_str } 
+                { error } 
                 </div>
                 <div class="toolbar">
                     <button class="btn-error" onclick=self.link.callback(|_| Msg::CloseError)>{"Ok"}</button>
@@ -328,13 +468,97 @@ impl Component for GamePage {
                Turn::Pregame => html! {
                 <div class="wrapper">
                     <div class="toolbar">
-                    {if !self.my_state().ready  {
+                    {if is_seated && !self.my_state().ready  {
                         html! {<button class="primary" onclick=self.link.callback(|_| Msg::MarkReady)>{ tr!("Ready!")}</button>}
                     } else {
                         html! {}
+                    }}
+                    {if self.previous_game.is_some() {
+                        html! {<button onclick=self.link.callback(|_| Msg::Restore)>{ tr!("Resume my last game")}</button>}
+                    } else {
+                        html! {}
                     }}
                         <button class="cancel" onclick=self.link.callback(|_| Msg::Disconnect)>{ tr!("Disconnect") }</button>
                     </div>
+                    <div class="toolbar">
+                    { for [MatchEnd::TargetScore(50.0), MatchEnd::TargetScore(100.0), MatchEnd::Deals(5)].iter().map(|match_end| {
+                        let match_end = *match_end;
+                        let label = match match_end {
+                            MatchEnd::TargetScore(target) => tr!("{0} points", target),
+                            MatchEnd::Deals(count) => tr!("{0} deals", count),
+                        };
+                        let class = if self.game_state.options.match_end == match_end { "primary" } else { "" };
+                        html! {
+                            <button class=class onclick=self.link.callback(move |_| Msg::SetMatchEnd(match_end))>{ label }</button>
+                        }
+                    }) }
+                    </div>
+                    <div class="toolbar">
+                    { for (MIN_PLAYERS..=MAX_PLAYERS).map(|nb_players| {
+                        let class = if self.game_state.options.nb_players == nb_players { "primary" } else { "" };
+                        html! {
+                            <button class=class onclick=self.link.callback(move |_| Msg::SetNbPlayers(nb_players))>{ tr!("{0} players", nb_players) }</button>
+                        }
+                    }) }
+                    </div>
+                    <div class="toolbar">
+                    {{
+                        let open_table = self.game_state.options.open_table;
+                        let class = if open_table { "primary" } else { "" };
+                        html! {
+                            <button class=class onclick=self.link.callback(move |_| Msg::SetOpenTable(!open_table))>{ tr!("Spectators see all hands") }</button>
+                        }
+                    }}
+                    </div>
+                    <div class="toolbar">
+                    { for [TimeControl::Unlimited, TimeControl::Bank { total: 300, increment: 5 }, TimeControl::PerMove(30)].iter().map(|time_control| {
+                        let time_control = *time_control;
+                        let label = match time_control {
+                            TimeControl::Unlimited => tr!("no time limit"),
+                            TimeControl::Bank { total, increment } => tr!("{0} min + {1} s per card", total / 60, increment),
+                            TimeControl::PerMove(seconds) => tr!("{0} s per card", seconds),
+                        };
+                        let class = if self.game_state.options.time_control == time_control { "primary" } else { "" };
+                        html! {
+                            <button class=class onclick=self.link.callback(move |_| Msg::SetTimeControl(time_control))>{ label }</button>
+                        }
+                    }) }
+                    </div>
+                    { if self.game_state.options.time_control != TimeControl::Unlimited {
+                        html! {
+                    <div class="toolbar">
+                    { for [TimeoutAction::AutoPlay, TimeoutAction::ForfeitDeal].iter().map(|action| {
+                        let action = *action;
+                        let label = match action {
+                            TimeoutAction::AutoPlay => tr!("a card is played when time is up"),
+                            TimeoutAction::ForfeitDeal => tr!("the deal is lost when time is up"),
+                        };
+                        let class = if self.game_state.options.timeout_action == action { "primary" } else { "" };
+                        html! {
+                            <button class=class onclick=self.link.callback(move |_| Msg::SetTimeoutAction(action))>{ label }</button>
+                        }
+                    }) }
+                    </div>
+                        }
+                    } else { html! {} }}
+                    <div class="toolbar">
+                        <input value=&self.seed_input
+                            size="64"
+                            placeholder=tr!("seed")
+                            oninput=self.link.callback(|e: InputData| Msg::SetSeedInput(e.value)) />
+                        <button onclick=self.link.callback(|_| Msg::SetSeed)>{ tr!("Use seed") }</button>
+                    </div>
+                    { for self.game_state.options.seed.iter().map(|seed| html! {
+                        <div class="seed">{ tr!("seed of the game: {0}", format_seed(seed)) }</div>
+                    }) }
+                    <div class="toolbar">
+                    { for StrategyKind::ALL.iter().map(|strategy| {
+                        let strategy = *strategy;
+                        html! {
+                            <button onclick=self.link.callback(move |_| Msg::AddBot(strategy))>{ tr!("Add a {0} bot", strategy.name()) }</button>
+                        }
+                    }) }
+                    </div>
                     <h1>{{ tr!("join code:") }} <strong>{format!(" {}", format_join_code(&self.game_info.join_code))}</strong></h1>
                  </div>
                 },
@@ -342,7 +566,7 @@ impl Component for GamePage {
                     html! {
                         <div>
                             {if let Some(card) = card_played {
-                                let style =format!("--bg-image: url('cards/{}-{}.svg')", &card.rank().to_string(), &card.suit().to_safe_string());
+                                let style =format!("--bg-image: url('cards/{}.svg')", card.to_safe_string());
                                 html! {
                                     <div class="card" style={style}></div>
                                 }
@@ -361,7 +585,7 @@ impl Component for GamePage {
         { if self.game_state.turn != Turn::Pregame && self.game_state.turn != Turn::Interdeal {
             html! {
               for self.hand.list().iter().map(|card| {
-                let style =format!("--bg-image: url('cards/{}-{}.svg')", &card.rank().to_string(), &card.suit().to_safe_string());
+                let style =format!("--bg-image: url('cards/{}.svg')", card.to_safe_string());
                 let clicked = card.clone();
                 html! {
                     <div class="card" style={style} 
@@ -383,3 +607,23 @@ impl Component for GamePage {
         }
     }
 }
+
+/// Returns the translated text of an error from the server.
+fn error_text(error: &ProtocolError) -> String {
+    match error.code() {
+        ErrorCode::NotInGame => tr!("not in a game"),
+        ErrorCode::GameStarted => tr!("the game has already started"),
+        ErrorCode::InvalidRules => tr!("invalid rules: {0}", error.params().join(", ")),
+        ErrorCode::TooManyPlayers => tr!("too many players seated"),
+        ErrorCode::NoFreeSeat => tr!("no free seat"),
+        ErrorCode::SpectatorCantPlay => tr!("spectators can't play"),
+        ErrorCode::InvalidTurnOrder => tr!("invalid turn order"),
+        ErrorCode::CardMissing => tr!("you can only play cards you have"),
+        ErrorCode::IncorrectSuit => tr!("wrong suit played"),
+        ErrorCode::JokerFirstTrick => tr!("jokers can't be played on the first trick"),
+        ErrorCode::NoLastTrick => tr!("no trick has been played yet"),
+        ErrorCode::UnknownSavedGame => tr!("no saved game to resume"),
+        ErrorCode::UnknownStrategy => tr!("unknown bot strategy"),
+        ErrorCode::UnknownDealState | ErrorCode::Unknown => error.message().to_string(),
+    }
+}
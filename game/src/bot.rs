@@ -0,0 +1,164 @@
+//! Computer players.
+//!
+//! A strategy only sees what a player at the table can see: its own hand
+//! and the cards played so far.
+
+use rand::rngs::StdRng;
+use rand::seq::IteratorRandom;
+use rand::{thread_rng, SeedableRng};
+
+use super::cards;
+use super::deal;
+use super::pos;
+use super::rules;
+use super::trick;
+
+/// What a player knows of a deal when it is its turn to play.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observation {
+    /// Position of the player
+    pub pos: pos::PlayerPos,
+    /// Cards of the player
+    pub hand: cards::Hand,
+    /// Cards the player is allowed to play
+    pub legal_moves: cards::Hand,
+    /// Tricks of the deal, the last one is the current trick
+    pub tricks: Vec<trick::Trick>,
+    /// Number of cards left in the stock
+    pub stock_size: usize,
+    /// Points won so far by each player
+    pub points: Vec<f32>,
+    pub rules: rules::RuleSet,
+}
+
+impl Observation {
+    /// Returns the view of `state` from the seat `pos`.
+    pub fn new(state: &deal::DealState, pos: pos::PlayerPos) -> Self {
+        Observation {
+            pos,
+            hand: state.hands()[pos as usize],
+            legal_moves: state.legal_moves(pos),
+            tricks: state.tricks().to_vec(),
+            stock_size: state.stock_size(),
+            points: state.points(),
+            rules: state.rules().clone(),
+        }
+    }
+
+    pub fn nb_players(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Returns the trick being played.
+    pub fn current_trick(&self) -> &trick::Trick {
+        &self.tricks[self.tricks.len() - 1]
+    }
+
+    /// Returns `true` if playing `card` would make the player win the
+    /// current trick, for now.
+    pub fn wins_trick(&self, card: cards::Card) -> bool {
+        let mut trick = self.current_trick().clone();
+        trick.play_card(self.pos, card, &self.rules);
+        trick.winner == self.pos
+    }
+}
+
+/// Chooses the card a computer player plays.
+pub trait Strategy {
+    /// Returns a card among `observation.legal_moves`.
+    fn play(&mut self, observation: &Observation) -> cards::Card;
+}
+
+/// Plays a random legal card.
+pub struct RandomStrategy;
+
+impl Strategy for RandomStrategy {
+    fn play(&mut self, observation: &Observation) -> cards::Card {
+        observation.legal_moves.iter().choose(&mut thread_rng()).expect("no legal move")
+    }
+}
+
+/// Plays a random legal card, making the same choices for a given seed.
+pub struct SeededRandomStrategy {
+    rng: StdRng,
+}
+
+impl SeededRandomStrategy {
+    pub fn new(seed: super::Seed) -> Self {
+        SeededRandomStrategy {
+            rng: StdRng::from_seed(seed),
+        }
+    }
+}
+
+impl Strategy for SeededRandomStrategy {
+    fn play(&mut self, observation: &Observation) -> cards::Card {
+        observation.legal_moves.iter().choose(&mut self.rng).expect("no legal move")
+    }
+}
+
+/// Wins the trick with its strongest card when possible, otherwise plays
+/// its weakest card.
+pub struct GreedyStrategy;
+
+impl Strategy for GreedyStrategy {
+    fn play(&mut self, observation: &Observation) -> cards::Card {
+        let rules = &observation.rules;
+        let moves = observation.legal_moves;
+        let strongest = moves.max_by_strength(|c| rules.strength(c)).expect("no legal move");
+        if observation.wins_trick(strongest) {
+            strongest
+        } else {
+            moves.min_by_strength(|c| rules.strength(c)).expect("no legal move")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::{Card, Rank, Suit};
+    use crate::pos::PlayerPos;
+
+    #[test]
+    fn test_greedy() {
+        let mut hands = vec![cards::Hand::new(); 2];
+        let heart_2 = Card::new(Suit::Heart, Rank::Rank2);
+        let heart_5 = Card::new(Suit::Heart, Rank::Rank5);
+        let heart_9 = Card::new(Suit::Heart, Rank::Rank9);
+        let spade_4 = Card::new(Suit::Spade, Rank::Rank4);
+        hands[0].add(heart_5);
+        hands[1].add(heart_2).add(heart_9).add(spade_4);
+        let mut state = deal::DealState::new(PlayerPos::P0, hands, cards::Deck::empty(), rules::RuleSet::default());
+        state.play_card(PlayerPos::P0, heart_5).unwrap();
+
+        // Can win: strongest heart
+        let observation = Observation::new(&state, PlayerPos::P1);
+        assert_eq!(observation.legal_moves.size(), 2);
+        assert_eq!(GreedyStrategy.play(&observation), heart_9);
+
+        // Can't win: weakest card
+        let mut observation = observation;
+        observation.legal_moves.remove(heart_9);
+        assert_eq!(GreedyStrategy.play(&observation), heart_2);
+    }
+
+    #[test]
+    fn test_strategies_play_legal_moves() {
+        let strategies: Vec<Box<dyn Strategy>> = vec![
+            Box::new(RandomStrategy),
+            Box::new(SeededRandomStrategy::new([1; 32])),
+            Box::new(GreedyStrategy),
+        ];
+        for mut strategy in strategies {
+            let rules = rules::RuleSet::default();
+            let (hands, stock) = crate::deal_seeded_hands([2; 32], 3, &rules);
+            let mut state = deal::DealState::new(PlayerPos::P0, hands, stock, rules);
+            while !state.is_over() {
+                let pos = state.next_player();
+                let card = strategy.play(&Observation::new(&state, pos));
+                assert!(state.play_card(pos, card).is_ok());
+            }
+        }
+    }
+}
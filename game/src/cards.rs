@@ -1,13 +1,13 @@
 //! This module represents a basic, rule-agnostic 54-cards system.
 
-use rand::{thread_rng, SeedableRng};
+use rand::{thread_rng, Rng, SeedableRng};
 use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
 
 use serde::{Deserialize, Serialize};
-use std::num::Wrapping;
+use std::fmt;
+use std::ops::{BitAnd, BitOr, Sub};
 use std::str::FromStr;
-use std::string::ToString;
 
 /// One of the four Suits: Heart, Spade, Diamond, Club.
 #[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
@@ -38,7 +38,7 @@ impl Suit {
     ///
     /// # Panics
     ///
-    /// If `n >= 5`.
+    /// If `n >= 6`.
     pub fn from_n(n: u32) -> Self {
         match n {
             0 => Suit::Heart,
@@ -51,18 +51,6 @@ impl Suit {
         }
     }
 
-    /// Returns a UTF-8 character representing the suit (♥, ♠, ♦ or ♣).
-    pub fn to_string(self) -> String {
-        match self {
-            Suit::Heart => "♥",
-            Suit::Spade => "♠",
-            Suit::Diamond => "♦",
-            Suit::Club => "♣",
-            Suit::RedJoker => "☆",
-            Suit::BlackJoker => "★",
-        }.to_owned()
-    }
-
     /// Returns a character representing the suit (H, S, D or C).
     pub fn to_safe_string(self) -> String {
         match self {
@@ -74,6 +62,36 @@ impl Suit {
             Suit::BlackJoker => "bJ",
         }.to_owned()
     }
+
+    /// Returns `true` for the two joker suits.
+    pub fn is_joker(self) -> bool {
+        self == Suit::RedJoker || self == Suit::BlackJoker
+    }
+
+    // Returns the bits of all the cards of this suit.
+    fn mask(self) -> u64 {
+        if self.is_joker() {
+            // A joker suit only has a single card
+            self as u64
+        } else {
+            RANK_MASK * self as u64
+        }
+    }
+}
+
+impl fmt::Display for Suit {
+    /// Writes a UTF-8 character representing the suit (♥, ♠, ♦ or ♣).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Suit::Heart => "♥",
+            Suit::Spade => "♠",
+            Suit::Diamond => "♦",
+            Suit::Club => "♣",
+            Suit::RedJoker => "☆",
+            Suit::BlackJoker => "★",
+        };
+        write!(f, "{}", s)
+    }
 }
 
 impl FromStr for Suit {
@@ -85,15 +103,15 @@ impl FromStr for Suit {
             "♣" | "C" | "c" | "club" | "Suit::Club" | "Club" => Ok(Suit::Club),
             "♠" | "S" | "s" | "spade" | "Suit::Spade" | "Spade" => Ok(Suit::Spade),
             "♦" | "D" | "d" | "diamond" | "Suit::Diamond" | "Diamond" => Ok(Suit::Diamond),
-            "rJ" | "rj" | "red joker" | "Suit::RedJoker" | "Red Joker" => Ok(Suit::RedJoker),
-            "bJ" | "bj" | "black joker" | "Suit::BlackJoker" | "Black Joker" => Ok(Suit::BlackJoker),
+            "☆" | "rJ" | "rj" | "red joker" | "Suit::RedJoker" | "Red Joker" => Ok(Suit::RedJoker),
+            "★" | "bJ" | "bj" | "black joker" | "Suit::BlackJoker" | "Black Joker" => Ok(Suit::BlackJoker),
             _ => Err(format!("invalid suit: {}", s)),
         }
     }
 }
 
 /// Rank of a card in a suit.
-#[derive(PartialEq, Clone, Copy, Debug)]
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
 #[repr(u64)]
 pub enum Rank {
     Rank1 = 1,
@@ -112,7 +130,7 @@ pub enum Rank {
 }
 
 /// Bit RANK_MASK over all ranks
-const RANK_MASK: u64 = 16383; 
+const RANK_MASK: u64 = 8191;
 
 impl Rank {
     /// Returns the rank corresponding to the given number:
@@ -160,10 +178,12 @@ impl Rank {
             other => panic!("invalid rank discrimant: {}", other),
         }
     }
+}
 
-    /// Returns a character representing the given rank.
-    pub fn to_string(self) -> String {
-        match self {
+impl fmt::Display for Rank {
+    /// Writes a character representing the given rank.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
             Rank::Rank1 => "1",
             Rank::Rank2 => "2",
             Rank::Rank3 => "3",
@@ -177,12 +197,60 @@ impl Rank {
             Rank::RankJ => "J",
             Rank::RankQ => "Q",
             Rank::RankK => "K",
-        }.to_owned()
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Rank {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        match s {
+            "1" => Ok(Rank::Rank1),
+            "2" => Ok(Rank::Rank2),
+            "3" => Ok(Rank::Rank3),
+            "4" => Ok(Rank::Rank4),
+            "5" => Ok(Rank::Rank5),
+            "6" => Ok(Rank::Rank6),
+            "7" => Ok(Rank::Rank7),
+            "8" => Ok(Rank::Rank8),
+            "9" => Ok(Rank::Rank9),
+            "10" => Ok(Rank::Rank10),
+            "J" | "j" => Ok(Rank::RankJ),
+            "Q" | "q" => Ok(Rank::RankQ),
+            "K" | "k" => Ok(Rank::RankK),
+            _ => Err(ParseError::InvalidRank(s.to_owned())),
+        }
+    }
+}
+
+/// Error when reading a rank, a card or a hand from a string.
+#[derive(PartialEq, Clone, Debug)]
+pub enum ParseError {
+    /// The rank is not one of 1-10, J, Q or K
+    InvalidRank(String),
+    /// The suit is not one of the known suit names
+    InvalidSuit(String),
+    /// A joker was given another rank than 1
+    InvalidJoker(String),
+    /// The string is not a list of cards between brackets
+    InvalidHand(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::InvalidRank(s) => write!(f, "invalid rank: {}", s),
+            ParseError::InvalidSuit(s) => write!(f, "invalid suit: {}", s),
+            ParseError::InvalidJoker(s) => write!(f, "invalid joker: {}", s),
+            ParseError::InvalidHand(s) => write!(f, "invalid hand: {}", s),
+        }
     }
 }
 
 /// Represents a single card.
-#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize, Eq, Hash)]
 pub struct Card(u64);
 
 impl Card {
@@ -235,21 +303,53 @@ impl Card {
         }
     }
 
-    /// Returns a string representation of the card (ex: "7♦").
-    pub fn to_string(self) -> String {
-        let r = self.rank();
-        let s = self.suit();
-        r.to_string() + &s.to_string()
+    /// Returns an ASCII representation of the card (ex: "7-D"), as used for
+    /// the card images.
+    pub fn to_safe_string(self) -> String {
+        format!("{}-{}", self.rank(), self.suit().to_safe_string())
     }
 
     /// Creates a card from the given suit and rank.
     pub fn new(suit: Suit, rank: Rank) -> Self {
         Card(suit as u64 * rank as u64)
     }
+
+    /// Returns `true` if the card is one of the two jokers.
+    pub fn is_joker(self) -> bool {
+        self.0 >= Suit::RedJoker as u64
+    }
+}
+
+impl fmt::Display for Card {
+    /// Writes the card (ex: "7♦").
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.rank(), self.suit())
+    }
+}
+
+impl FromStr for Card {
+    type Err = ParseError;
+
+    /// Reads a card from its unicode form ("10♥") or its ASCII form ("10-H").
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        let (rank, suit) = match s.find('-') {
+            Some(idx) => (&s[..idx], &s[idx + 1..]),
+            None => match s.char_indices().last() {
+                Some((idx, _)) => (&s[..idx], &s[idx..]),
+                None => return Err(ParseError::InvalidRank(s.to_owned())),
+            },
+        };
+        let rank: Rank = rank.parse()?;
+        let suit: Suit = suit.parse().map_err(|_| ParseError::InvalidSuit(suit.to_owned()))?;
+        if suit.is_joker() && rank != Rank::Rank1 {
+            return Err(ParseError::InvalidJoker(s.to_owned()));
+        }
+        Ok(Card::new(suit, rank))
+    }
 }
 
 /// Represents an unordered set of cards.
-#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize, Default)]
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize, Default, Eq, Hash)]
 pub struct Hand(u64);
 
 impl Hand {
@@ -291,6 +391,8 @@ impl Hand {
     }
 
     /// Returns `true` if `self` contains all cards from a given rank
+    ///
+    /// Only the four regular suits are considered: jokers never count as a `Rank1`.
     pub fn has_all_rank(self, rank: Rank) -> bool {
         self.has(Card::new(Suit::Club, rank))
             && self.has(Card::new(Suit::Heart, rank))
@@ -300,7 +402,17 @@ impl Hand {
 
     /// Returns `true` if the hand contains any card of the given suit.
     pub fn has_any(self, suit: Suit) -> bool {
-        self.0 & (RANK_MASK * suit as u64) != 0
+        self.0 & suit.mask() != 0
+    }
+
+    /// Returns the cards of `self` from the given suit.
+    pub fn suit_cards(self, suit: Suit) -> Hand {
+        Hand(self.0 & suit.mask())
+    }
+
+    /// Returns the jokers of `self`.
+    pub fn jokers(self) -> Hand {
+        Hand(self.0 & (Suit::RedJoker as u64 | Suit::BlackJoker as u64))
     }
 
     /// Returns `true` if `self` contains no card.
@@ -316,54 +428,135 @@ impl Hand {
             return Card(0);
         }
 
-        let Hand(h) = self;
-        // Finds the rightmost bit, shifted to the left by 1.
-        // let n = 1 << (h.trailing_zeroes());
-        let n = Wrapping(h ^ (h - 1)) + Wrapping(1);
-        if n.0 == 0 {
-            // We got an overflow. This means the desired bit it the leftmost one.
-            Card::from_id(53)
-        } else {
-            // We just need to shift it back.
-            Card(n.0 >> 1)
-        }
+        // The rightmost bit
+        Card(1 << self.0.trailing_zeros())
+    }
+
+    /// Returns an iterator on the cards of `self`, in id order.
+    pub fn iter(self) -> HandIter {
+        HandIter(self.0)
     }
 
     /// Returns the cards contained in `self` as a `Vec`.
     pub fn list(self) -> Vec<Card> {
-        let mut cards = Vec::new();
-        let mut h = self;
+        self.iter().collect()
+    }
+
+    /// Returns the number of cards in `self`.
+    pub fn size(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Returns the weakest card of `self` according to `strength`, the one
+    /// with the lowest id on ties.
+    pub fn min_by_strength<F: Fn(Card) -> i32>(self, strength: F) -> Option<Card> {
+        self.iter().min_by_key(|card| strength(*card))
+    }
+
+    /// Returns the strongest card of `self` according to `strength`, the one
+    /// with the highest id on ties.
+    pub fn max_by_strength<F: Fn(Card) -> i32>(self, strength: F) -> Option<Card> {
+        self.iter().max_by_key(|card| strength(*card))
+    }
+}
+
+/// Iterates on the cards of a hand, without allocating.
+pub struct HandIter(u64);
 
-        while !h.is_empty() {
-            let c = h.get_card();
-            h.remove(c);
-            cards.push(c);
+impl Iterator for HandIter {
+    type Item = Card;
+
+    fn next(&mut self) -> Option<Card> {
+        if self.0 == 0 {
+            return None;
         }
+        let card = Card(1 << self.0.trailing_zeros());
+        // Clears the rightmost bit
+        self.0 &= self.0 - 1;
+        Some(card)
+    }
 
-        cards
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let size = self.0.count_ones() as usize;
+        (size, Some(size))
     }
+}
 
-    /// Returns the number of cards in `self`.
-    pub fn size(self) -> usize {
-        self.list().len()
+impl ExactSizeIterator for HandIter {}
+
+impl IntoIterator for Hand {
+    type Item = Card;
+    type IntoIter = HandIter;
+
+    fn into_iter(self) -> HandIter {
+        self.iter()
+    }
+}
+
+impl From<Card> for Hand {
+    fn from(card: Card) -> Hand {
+        Hand(card.0)
     }
 }
 
-impl ToString for Hand {
-    /// Returns a string representation of `self`.
-    fn to_string(&self) -> String {
-        let mut s = "[".to_owned();
+/// Cards in either hand.
+impl BitOr for Hand {
+    type Output = Hand;
+
+    fn bitor(self, other: Hand) -> Hand {
+        Hand(self.0 | other.0)
+    }
+}
+
+/// Cards in both hands.
+impl BitAnd for Hand {
+    type Output = Hand;
+
+    fn bitand(self, other: Hand) -> Hand {
+        Hand(self.0 & other.0)
+    }
+}
+
+/// Cards of the first hand missing from the second one.
+impl Sub for Hand {
+    type Output = Hand;
+
+    fn sub(self, other: Hand) -> Hand {
+        Hand(self.0 & !other.0)
+    }
+}
 
-        for c in &(*self).list() {
-            s += &c.to_string();
-            s += ",";
+impl fmt::Display for Hand {
+    /// Writes the cards of `self` (ex: "[7♦,K♥,]").
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        for c in self.iter() {
+            write!(f, "{},", c)?;
         }
+        write!(f, "]")
+    }
+}
+
+impl FromStr for Hand {
+    type Err = ParseError;
 
-        s + "]"
+    /// Reads a list of cards between brackets, separated by commas or spaces.
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        let trimmed = s.trim();
+        if trimmed.len() < 2 || !trimmed.starts_with('[') || !trimmed.ends_with(']') {
+            return Err(ParseError::InvalidHand(s.to_owned()));
+        }
+        let inner = &trimmed[1..trimmed.len() - 1];
+        let mut hand = Hand::new();
+        for card in inner.split(|c: char| c == ',' || c.is_whitespace()).filter(|c| !c.is_empty()) {
+            hand.add(card.parse()?);
+        }
+        Ok(hand)
     }
 }
 
 /// A deck of cards.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Deck {
     cards: Vec<Card>,
 }
@@ -381,25 +574,55 @@ impl Deck {
             cards: Vec::with_capacity(54),
         };
 
-        for i in 0..53 {
+        for i in 0..54 {
             d.cards.push(Card::from_id(i));
         }
 
         d
     }
 
+    /// Returns a full, sorted deck of 52 cards, without the jokers.
+    pub fn without_jokers() -> Self {
+        let mut d = Deck::new();
+        d.cards.retain(|card| !card.is_joker());
+        d
+    }
+
+    /// Returns a deck without any card.
+    pub fn empty() -> Self {
+        Deck { cards: Vec::new() }
+    }
+
+    /// Returns a deck made of `cards`, the last one being on top.
+    pub fn from_cards(cards: Vec<Card>) -> Self {
+        Deck { cards }
+    }
+
+    /// Returns the cards of this deck, the last one being on top.
+    pub fn cards(&self) -> &[Card] {
+        &self.cards
+    }
+
+    /// Puts `card` on top of the deck.
+    pub fn push(&mut self, card: Card) {
+        self.cards.push(card);
+    }
+
     /// Shuffle this deck.
     pub fn shuffle(&mut self) {
-        let mut rng = thread_rng();
-        self.cards[..].shuffle(&mut rng);
+        self.shuffle_with(&mut thread_rng());
     }
 
     /// Shuffle this deck with the given random seed.
     ///
     /// Result is determined by the seed.
     pub fn shuffle_seeded(&mut self, seed: [u8; 32]) {
-        let mut rng = StdRng::from_seed(seed);
-        self.cards[..].shuffle(&mut rng);
+        self.shuffle_with(&mut StdRng::from_seed(seed));
+    }
+
+    /// Shuffle this deck with the given random number generator.
+    pub fn shuffle_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        self.cards[..].shuffle(rng);
     }
 
     /// Draw the top card from the deck.
@@ -423,9 +646,9 @@ impl Deck {
     /// Deal `n` cards to each hand.
     ///
     /// # Panics
-    /// If `self.len() < 5 * n`
-    pub fn deal_each(&mut self, hands: &mut [Hand; super::NB_PLAYERS], n: usize) {
-        if self.len() < super::NB_PLAYERS * n {
+    /// If `self.len() < hands.len() * n`
+    pub fn deal_each(&mut self, hands: &mut [Hand], n: usize) {
+        if self.len() < hands.len() * n {
             panic!("Deck has too few cards!");
         }
 
@@ -437,16 +660,13 @@ impl Deck {
     }
 }
 
-impl ToString for Deck {
-    fn to_string(&self) -> String {
-        let mut s = "[".to_owned();
-
+impl fmt::Display for Deck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
         for c in &self.cards {
-            s += &c.to_string();
-            s += ",";
+            write!(f, "{},", c)?;
         }
-
-        s + "]"
+        write!(f, "]")
     }
 }
 
@@ -461,11 +681,46 @@ mod tests {
 
         let card = Card::from_id(52);
         assert!(Card::new(Suit::RedJoker, Rank::Rank1) == card);
+        assert!(card.is_joker());
+
+        let card = Card::new(Suit::BlackJoker, Rank::Rank1);
+        assert!(53 == card.id());
+
+        let card = Card::from_id(53);
+        assert!(Card::new(Suit::BlackJoker, Rank::Rank1) == card);
+        assert!(card.is_joker());
+
+        assert!(!Card::from_id(51).is_joker());
+    }
+
+    #[test]
+    fn test_parse() {
+        for id in 0..54 {
+            let card = Card::from_id(id);
+            assert_eq!(card.to_string().parse(), Ok(card));
+            assert_eq!(card.to_safe_string().parse(), Ok(card));
+        }
+        assert_eq!("10-h".parse(), Ok(Card::new(Suit::Heart, Rank::Rank10)));
+        assert_eq!("11♥".parse::<Card>(), Err(ParseError::InvalidRank("11".to_owned())));
+        assert_eq!("7-X".parse::<Card>(), Err(ParseError::InvalidSuit("X".to_owned())));
+        assert_eq!("K-rJ".parse::<Card>(), Err(ParseError::InvalidJoker("K-rJ".to_owned())));
+        assert!("".parse::<Card>().is_err());
+
+        let mut hand = Hand::new();
+        assert_eq!(hand.to_string().parse(), Ok(hand));
+        hand.add(Card::new(Suit::Diamond, Rank::Rank7));
+        hand.add(Card::new(Suit::Heart, Rank::RankK));
+        hand.add(Card::new(Suit::BlackJoker, Rank::Rank1));
+        assert_eq!(hand.to_string().parse(), Ok(hand));
+        assert_eq!("[7-D K-H 1-bJ]".parse(), Ok(hand));
+        assert_eq!("7♦".parse::<Hand>(), Err(ParseError::InvalidHand("7♦".to_owned())));
+        assert_eq!("[".parse::<Hand>(), Err(ParseError::InvalidHand("[".to_owned())));
+        assert_eq!(" [] ".parse(), Ok(Hand::new()));
     }
 
     #[test]
     fn test_cards() {
-        for i in 0..53 {
+        for i in 0..54 {
             let card = Card::from_id(i);
             assert!(i == card.id());
         }
@@ -544,6 +799,77 @@ mod tests {
         assert!(!hand.has_any(Suit::Heart));
         assert!(hand.has_any(Suit::Club));
         assert!(hand.has_any(Suit::BlackJoker));
+        assert!(!hand.has_any(Suit::RedJoker));
+
+        let mut hand = Hand::new();
+        hand.add(Card::new(Suit::RedJoker, Rank::Rank1));
+        hand.add(Card::new(Suit::Spade, Rank::Rank1));
+        assert!(!hand.has_any(Suit::Club));
+        assert!(!hand.has_any(Suit::Heart));
+    }
+
+    #[test]
+    fn test_suit_mask() {
+        // The mask of a suit covers its 13 ranks, up to the king, and no
+        // card of the next suit
+        for n in 0..4 {
+            let suit = Suit::from_n(n);
+            let king = Card::new(suit, Rank::RankK);
+            let mut hand = Hand::new();
+            hand.add(king);
+            assert!(hand.has_any(suit));
+            assert_eq!(hand.suit_cards(suit), hand);
+
+            let next = if n < 3 {
+                Card::new(Suit::from_n(n + 1), Rank::Rank1)
+            } else {
+                Card::new(Suit::RedJoker, Rank::Rank1)
+            };
+            let mut hand = Hand::new();
+            hand.add(next);
+            assert!(!hand.has_any(suit));
+            assert!(hand.suit_cards(suit).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_hand_ops() {
+        let spade_1 = Card::new(Suit::Spade, Rank::Rank1);
+        let spade_k = Card::new(Suit::Spade, Rank::RankK);
+        let club_6 = Card::new(Suit::Club, Rank::Rank6);
+        let joker = Card::new(Suit::BlackJoker, Rank::Rank1);
+        let hand = Hand::from(spade_k) | spade_1.into() | club_6.into() | joker.into();
+
+        assert_eq!(hand.size(), 4);
+        assert_eq!(hand.iter().len(), 4);
+        assert_eq!(hand.iter().collect::<Vec<_>>(), vec![spade_1, spade_k, club_6, joker]);
+        assert_eq!(hand.get_card(), spade_1);
+        assert_eq!(Hand::from(joker).get_card(), joker);
+        assert_eq!(Hand::new().size(), 0);
+
+        assert_eq!(hand.suit_cards(Suit::Spade), Hand::from(spade_1) | spade_k.into());
+        assert_eq!(hand.suit_cards(Suit::Heart), Hand::new());
+        assert_eq!(hand.jokers(), Hand::from(joker));
+        assert_eq!(hand & Hand::from(club_6), Hand::from(club_6));
+        assert_eq!(hand - hand.suit_cards(Suit::Spade), Hand::from(club_6) | joker.into());
+
+        let strength = |card: Card| card.id() as i32 % 13;
+        assert_eq!(hand.min_by_strength(strength), Some(spade_1));
+        assert_eq!(hand.max_by_strength(strength), Some(spade_k));
+        assert_eq!(Hand::new().max_by_strength(strength), None);
+    }
+
+    #[test]
+    fn test_has_all_rank() {
+        let mut hand = Hand::new();
+        for s in 0..4 {
+            hand.add(Card::new(Suit::from_n(s), Rank::Rank1));
+        }
+        assert!(hand.has_all_rank(Rank::Rank1));
+        hand.add(Card::new(Suit::RedJoker, Rank::Rank1));
+        assert!(hand.has_all_rank(Rank::Rank1));
+        hand.remove(Card::new(Suit::Heart, Rank::Rank1));
+        assert!(!hand.has_all_rank(Rank::Rank1));
     }
 
     #[test]
@@ -567,21 +893,23 @@ mod tests {
 
 #[cfg(feature = "use_bench")]
 mod benchs {
-    use deal_seeded_hands;
+    use crate::rules::RuleSet;
+    use crate::{deal_seeded_hands, NB_PLAYERS};
     use test::Bencher;
 
+    const SEED: [u8; 32] = [1; 32];
+
     #[bench]
     fn bench_deal(b: &mut Bencher) {
-        let seed = &[1, 2, 3, 4, 5];
+        let rules = RuleSet::default();
         b.iter(|| {
-            deal_seeded_hands(seed);
+            deal_seeded_hands(SEED, NB_PLAYERS, &rules);
         });
     }
 
     #[bench]
     fn bench_list_hand(b: &mut Bencher) {
-        let seed = &[1, 2, 3, 4, 5];
-        let hands = deal_seeded_hands(seed);
+        let (hands, _) = deal_seeded_hands(SEED, NB_PLAYERS, &RuleSet::default());
         b.iter(|| {
             for hand in hands.iter() {
                 hand.list().len();
@@ -589,10 +917,19 @@ mod benchs {
         });
     }
 
+    #[bench]
+    fn bench_iter_hand(b: &mut Bencher) {
+        let (hands, _) = deal_seeded_hands(SEED, NB_PLAYERS, &RuleSet::default());
+        b.iter(|| {
+            for hand in hands.iter() {
+                hand.iter().count();
+            }
+        });
+    }
+
     #[bench]
     fn bench_del_add_check(b: &mut Bencher) {
-        let seed = &[1, 2, 3, 4, 5];
-        let hands = deal_seeded_hands(seed);
+        let (hands, _) = deal_seeded_hands(SEED, NB_PLAYERS, &RuleSet::default());
         let cards: Vec<_> = hands.iter().map(|h| h.list()).collect();
         b.iter(|| {
             let mut hands = hands.clone();
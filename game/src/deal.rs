@@ -1,17 +1,25 @@
 //! Module for the card deal, after auctions are complete.
 use std::fmt;
 
+use serde::{Deserialize, Serialize};
+
 use super::cards;
+use super::points;
 use super::pos;
+use super::rules;
 use super::trick;
 
 /// Describes the state of a deal, ready to play a card.
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct DealState {
-    players: [cards::Hand; super::NB_PLAYERS],
+    #[serde(deserialize_with = "super::deserialize_version")]
+    version: u32,
+    players: Vec<cards::Hand>,
     current: pos::PlayerPos,
-    points: [f32; super::NB_PLAYERS],
+    points: points::Tally,
     tricks: Vec<trick::Trick>,
+    stock: cards::Deck,
+    rules: rules::RuleSet,
 }
 
 /// Result of a deal.
@@ -23,7 +31,7 @@ pub enum DealResult {
     /// The deal is over
     GameOver {
         /// Worth of won tricks
-        points: [f32; super::NB_PLAYERS],
+        points: Vec<f32>,
     },
 }
 
@@ -43,7 +51,9 @@ pub enum PlayError {
     CardMissing,
     /// A player tried to play the wrong suit, while he still have some
     IncorrectSuit,
-    /// A player tried to play the wrong suit, while he still have trumps
+    /// A player tried to play a joker during the first trick
+    JokerFirstTrick,
+    /// A player asked for the last trick while none is finished yet
     NoLastTrick,
 }
 
@@ -53,22 +63,76 @@ impl fmt::Display for PlayError {
             PlayError::TurnError => write!(f, "invalid turn order"),
             PlayError::CardMissing => write!(f, "you can only play cards you have"),
             PlayError::IncorrectSuit => write!(f, "wrong suit played"),
+            PlayError::JokerFirstTrick => write!(f, "jokers can't be played on the first trick"),
             PlayError::NoLastTrick => write!(f, "no trick has been played yet"),
         }
     }
 }
 
 impl DealState {
-    /// Creates a new DealState, with the given cards, first player and contract.
-    pub fn new(first: pos::PlayerPos, hands: [cards::Hand; super::NB_PLAYERS]) -> Self {
+    /// Creates a new DealState, with the given cards, first player, stock to draw from
+    /// and rules.
+    ///
+    /// There are as many players as there are hands.
+    pub fn new(first: pos::PlayerPos, hands: Vec<cards::Hand>, stock: cards::Deck, rules: rules::RuleSet) -> Self {
+        let nb_players = hands.len();
         DealState {
+            version: super::STATE_VERSION,
             players: hands,
             current: first,
-            tricks: vec![trick::Trick::new(first)],
-            points: [0.0; 2],
+            tricks: vec![trick::Trick::new(first, nb_players)],
+            points: points::Tally::new(nb_players),
+            stock,
+            rules,
         }
     }
 
+    /// Creates a deal already in progress, from the cards held by each player,
+    /// the stock and the tricks played so far, the last one being the current trick.
+    ///
+    /// Points are counted from the finished tricks.
+    ///
+    /// # Panics
+    /// If `tricks` is empty.
+    pub fn from_position(
+        hands: Vec<cards::Hand>,
+        stock: cards::Deck,
+        tricks: Vec<trick::Trick>,
+        rules: rules::RuleSet,
+    ) -> Self {
+        let nb_players = hands.len();
+        let current_trick = tricks.last().expect("no trick");
+        let played = current_trick.cards.iter().flatten().count();
+        let (finished, current) = if played == nb_players {
+            (tricks.len(), current_trick.winner)
+        } else {
+            (tricks.len() - 1, current_trick.first.next_n(played, nb_players))
+        };
+        let mut points = points::Tally::new(nb_players);
+        for trick in &tricks[..finished] {
+            points.add_trick(trick);
+        }
+        DealState {
+            version: super::STATE_VERSION,
+            players: hands,
+            current,
+            tricks,
+            points,
+            stock,
+            rules,
+        }
+    }
+
+    /// Returns the rules this deal is played with.
+    pub fn rules(&self) -> &rules::RuleSet {
+        &self.rules
+    }
+
+    /// Returns the number of players in this deal.
+    pub fn nb_players(&self) -> usize {
+        self.players.len()
+    }
+
     /// Try to play a card
     pub fn play_card(
         &mut self,
@@ -91,7 +155,8 @@ impl DealState {
         )?;
 
         // Play the card
-        let trick_over = self.current_trick_mut().play_card(player, card);
+        let i = self.tricks.len() - 1;
+        let trick_over = self.tricks[i].play_card(player, card, &self.rules);
 
         // Remove card from player hand
         self.players[player as usize].remove(card);
@@ -99,38 +164,118 @@ impl DealState {
         // Is the trick over?
         let result = if trick_over {
             let winner = self.current_trick().winner;
+            let trick = self.current_trick().clone();
+            self.points.add_trick(&trick);
+            self.draw_cards(winner);
 
-            if self.tricks.len() == super::DEAL_SIZE {
-                // TODO petit au bout ? -> maj annonce
-            } else {
-                self.tricks.push(trick::Trick::new(winner));
+            if !self.is_over() {
+                self.tricks.push(trick::Trick::new(winner, self.nb_players()));
             }
             self.current = winner;
             TrickResult::TrickOver(winner, self.get_deal_result())
         } else {
-            self.current = self.current.next();
+            self.current = self.current.next(self.nb_players());
             TrickResult::Nothing
         };
 
         Ok(result)
     }
 
+    /// Returns the cards `pos` is allowed to play on the current trick.
+    ///
+    /// This does not check whether it is actually `pos`'s turn.
+    pub fn legal_moves(&self, pos: pos::PlayerPos) -> cards::Hand {
+        let hand = self.players[pos as usize];
+        let jokers = if self.tricks.len() == 1 && hand != hand.jokers() {
+            cards::Hand::new()
+        } else {
+            hand.jokers()
+        };
+        // Same rules as `can_play`, on the whole hand at once
+        match self.current_trick().suit() {
+            Some(suit) if hand.has_any(suit) => hand.suit_cards(suit) | jokers,
+            _ => (hand - hand.jokers()) | jokers,
+        }
+    }
+
+    /// Returns `true` if `pos` is allowed to play `card` on the current trick.
+    ///
+    /// This does not check whether it is actually `pos`'s turn.
+    pub fn is_legal(&self, pos: pos::PlayerPos, card: cards::Card) -> bool {
+        can_play(
+            pos,
+            card,
+            self.players[pos as usize],
+            self.current_trick(),
+            self.tricks.len() == 1,
+        ).is_ok()
+    }
+
+    /// Each player draws a card from the stock, starting with the trick winner.
+    ///
+    /// Nobody draws once the stock can't serve every player.
+    fn draw_cards(&mut self, winner: pos::PlayerPos) {
+        let nb_players = self.nb_players();
+        if self.stock.len() < nb_players {
+            return;
+        }
+        for pos in winner.until_n(nb_players, nb_players) {
+            let card = self.stock.draw();
+            self.players[pos as usize].add(card);
+        }
+    }
+
+    /// Returns the number of cards left in the stock.
+    pub fn stock_size(&self) -> usize {
+        self.stock.len()
+    }
+
+    /// Returns the cards left in the stock, the next one drawn being the last.
+    pub fn stock(&self) -> &cards::Deck {
+        &self.stock
+    }
+
     /// Returns the player expected to play next.
     pub fn next_player(&self) -> pos::PlayerPos {
         self.current
     }
 
+    /// Returns the final points if the deal is over.
     pub fn get_deal_result(&self) -> DealResult {
-        DealResult::Nothing
+        if !self.is_over() {
+            return DealResult::Nothing;
+        }
+
+        DealResult::GameOver {
+            points: self.points(),
+        }
+    }
+
+    /// Returns the points won so far by each player.
+    ///
+    /// The last trick bonus is included once the deal is over.
+    pub fn points(&self) -> Vec<f32> {
+        let mut points = self.points.points(&self.rules);
+        if self.is_over() {
+            let winner = self.current_trick().winner;
+            points[winner as usize] += self.rules.scoring.last_trick_bonus;
+        }
+        points
+    }
+
+    /// Returns the cards won so far by `pos`.
+    pub fn won_cards(&self, pos: pos::PlayerPos) -> cards::Hand {
+        self.points.won_cards(pos)
     }
 
     /// Returns the cards of all players
-    pub fn hands(&self) -> [cards::Hand; super::NB_PLAYERS] {
-        self.players
+    pub fn hands(&self) -> &[cards::Hand] {
+        &self.players
     }
 
+    /// Returns `true` once every player has played all his cards.
     pub fn is_over(&self) -> bool {
-        self.tricks.len() == super::DEAL_SIZE && !self.tricks[super::DEAL_SIZE -1].cards.iter().any(|&c| c.is_none())
+        self.players.iter().all(|hand| hand.is_empty())
     }
 
     /// Return the last trick, if possible
@@ -143,30 +288,280 @@ impl DealState {
         }
     }
 
+    /// Returns the tricks of the deal, the last one being the current trick.
+    pub fn tricks(&self) -> &[trick::Trick] {
+        &self.tricks
+    }
+
     /// Returns the current trick.
     pub fn current_trick(&self) -> &trick::Trick {
         let i = self.tricks.len() - 1;
         &self.tricks[i]
     }
-
-    fn current_trick_mut(&mut self) -> &mut trick::Trick {
-        let i = self.tricks.len() - 1;
-        &mut self.tricks[i]
-    }
 }
 
-/// Returns `true` if the move appear legal.
+/// Returns `Ok(())` if the move is legal, or the rule it breaks.
+///
+/// * the card must be in the player's hand ;
+/// * jokers can't be played on the first trick of the deal, unless the player
+///   holds nothing else ;
+/// * a regular card must follow the suit of the trick when the player has some.
+///   Jokers are exempt, and the first regular card played after a led joker
+///   sets the suit to follow.
 pub fn can_play(
     _p: pos::PlayerPos,
     card: cards::Card,
     hand: cards::Hand,
-    _trick: &trick::Trick,
-    _is_first_trick:bool,
+    trick: &trick::Trick,
+    is_first_trick:bool,
 ) -> Result<(), PlayError> {
     // First, we need the card to be able to play
     if !hand.has(card) {
         return Err(PlayError::CardMissing);
     }
 
+    if card.is_joker() {
+        if is_first_trick && hand != hand.jokers() {
+            return Err(PlayError::JokerFirstTrick);
+        }
+        return Ok(());
+    }
+
+    if let Some(suit) = trick.suit() {
+        if card.suit() != suit && hand.has_any(suit) {
+            return Err(PlayError::IncorrectSuit);
+        }
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::{Card, Hand, Rank, Suit};
+    use crate::pos::PlayerPos;
+
+    fn hand_of(cards: &[Card]) -> Hand {
+        let mut hand = Hand::new();
+        for card in cards {
+            hand.add(*card);
+        }
+        hand
+    }
+
+    #[test]
+    fn test_can_play() {
+        let heart_5 = Card::new(Suit::Heart, Rank::Rank5);
+        let heart_k = Card::new(Suit::Heart, Rank::RankK);
+        let club_7 = Card::new(Suit::Club, Rank::Rank7);
+        let joker = Card::new(Suit::RedJoker, Rank::Rank1);
+        let hand = hand_of(&[heart_k, club_7, joker]);
+
+        let mut trick = trick::Trick::new(PlayerPos::P0, 2);
+        trick.play_card(PlayerPos::P0, heart_5, &rules::RuleSet::default());
+
+        assert_eq!(can_play(PlayerPos::P1, heart_5, hand, &trick, false), Err(PlayError::CardMissing));
+        assert_eq!(can_play(PlayerPos::P1, heart_k, hand, &trick, false), Ok(()));
+        assert_eq!(can_play(PlayerPos::P1, club_7, hand, &trick, false), Err(PlayError::IncorrectSuit));
+        assert_eq!(can_play(PlayerPos::P1, joker, hand, &trick, false), Ok(()));
+        assert_eq!(can_play(PlayerPos::P1, joker, hand, &trick, true), Err(PlayError::JokerFirstTrick));
+        assert_eq!(can_play(PlayerPos::P1, joker, hand_of(&[joker]), &trick, true), Ok(()));
+
+        // Without hearts, any card can be played
+        let hand = hand_of(&[club_7, joker]);
+        assert_eq!(can_play(PlayerPos::P1, club_7, hand, &trick, false), Ok(()));
+
+        // Nothing to follow after a joker
+        let mut trick = trick::Trick::new(PlayerPos::P0, 2);
+        trick.play_card(PlayerPos::P0, Card::new(Suit::BlackJoker, Rank::Rank1), &rules::RuleSet::default());
+        let hand = hand_of(&[heart_k, club_7]);
+        assert_eq!(can_play(PlayerPos::P1, club_7, hand, &trick, false), Ok(()));
+    }
+
+    #[test]
+    fn test_play_card() {
+        let mut hands = vec![Hand::new(); 2];
+        hands[0] = hand_of(&[Card::new(Suit::Spade, Rank::Rank3), Card::new(Suit::Club, Rank::Rank2)]);
+        hands[1] = hand_of(&[Card::new(Suit::Spade, Rank::Rank9), Card::new(Suit::Club, Rank::Rank4)]);
+        let mut deal = DealState::new(PlayerPos::P0, hands, cards::Deck::empty(), rules::RuleSet::default());
+
+        assert_eq!(deal.play_card(PlayerPos::P1, Card::new(Suit::Spade, Rank::Rank9)), Err(PlayError::TurnError));
+        assert_eq!(deal.play_card(PlayerPos::P0, Card::new(Suit::Spade, Rank::Rank3)), Ok(TrickResult::Nothing));
+        assert_eq!(deal.play_card(PlayerPos::P1, Card::new(Suit::Club, Rank::Rank4)), Err(PlayError::IncorrectSuit));
+        assert_eq!(
+            deal.play_card(PlayerPos::P1, Card::new(Suit::Spade, Rank::Rank9)),
+            Ok(TrickResult::TrickOver(PlayerPos::P1, DealResult::Nothing))
+        );
+        assert_eq!(deal.next_player(), PlayerPos::P1);
+        assert_eq!(deal.points(), vec![0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_draw_cards() {
+        let mut stock = cards::Deck::empty();
+        stock.push(Card::new(Suit::Diamond, Rank::Rank6));
+        stock.push(Card::new(Suit::Diamond, Rank::Rank2));
+        stock.push(Card::new(Suit::Heart, Rank::Rank1));
+
+        let mut hands = vec![Hand::new(); 2];
+        hands[0] = hand_of(&[Card::new(Suit::Spade, Rank::Rank3)]);
+        hands[1] = hand_of(&[Card::new(Suit::Spade, Rank::Rank9)]);
+        let mut deal = DealState::new(PlayerPos::P0, hands, stock, rules::RuleSet::default());
+
+        deal.play_card(PlayerPos::P0, Card::new(Suit::Spade, Rank::Rank3)).unwrap();
+        deal.play_card(PlayerPos::P1, Card::new(Suit::Spade, Rank::Rank9)).unwrap();
+
+        // The winner draws first, from the top of the stock
+        assert_eq!(deal.hands()[1], hand_of(&[Card::new(Suit::Heart, Rank::Rank1)]));
+        assert_eq!(deal.hands()[0], hand_of(&[Card::new(Suit::Diamond, Rank::Rank2)]));
+        assert_eq!(deal.stock_size(), 1);
+        assert!(!deal.is_over());
+
+        deal.play_card(PlayerPos::P1, Card::new(Suit::Heart, Rank::Rank1)).unwrap();
+        let result = deal.play_card(PlayerPos::P0, Card::new(Suit::Diamond, Rank::Rank2)).unwrap();
+
+        // Not enough cards left in the stock for everybody
+        assert_eq!(deal.stock_size(), 1);
+        assert!(deal.is_over());
+        assert_eq!(result, TrickResult::TrickOver(PlayerPos::P1, DealResult::GameOver { points: vec![0.0, 2.0] }));
+    }
+
+    #[test]
+    fn test_deal_result() {
+        let rules = rules::RuleSet::default();
+        for nb_players in crate::MIN_PLAYERS..=crate::MAX_PLAYERS {
+            let (hands, river) = crate::deal_seeded_hands([7; 32], nb_players, &rules);
+            let mut deal = DealState::new(PlayerPos::P0, hands.clone(), river.clone(), rules.clone());
+
+            let mut last_result = TrickResult::Nothing;
+            while !deal.is_over() {
+                let pos = deal.next_player();
+                let card = deal.legal_moves(pos).get_card();
+                last_result = deal.play_card(pos, card).unwrap();
+            }
+
+            // Every card but the ones the stock could not share has been played
+            assert!(deal.stock_size() < nb_players);
+            let played = hands.iter().map(|h| h.size()).sum::<usize>() + river.len() - deal.stock_size();
+            let mut won = cards::Hand::new();
+            for pos in PlayerPos::P0.until_n(nb_players, nb_players) {
+                won.merge(deal.won_cards(pos));
+            }
+            assert_eq!(won.size(), played);
+
+            let points = deal.points();
+            assert_eq!(points.len(), nb_players);
+            let total: f32 = points.iter().sum();
+            let tricks = played / nb_players;
+            assert_eq!(total, points::hand_score(won, &rules.scoring) + tricks as f32 * points::TRICK_POINTS);
+            match last_result {
+                TrickResult::TrickOver(_, DealResult::GameOver { points: final_points }) => {
+                    assert_eq!(final_points, points)
+                }
+                other => panic!("unexpected result {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_legal_moves() {
+        let spade_3 = Card::new(Suit::Spade, Rank::Rank3);
+        let spade_9 = Card::new(Suit::Spade, Rank::Rank9);
+        let club_4 = Card::new(Suit::Club, Rank::Rank4);
+        let heart_j = Card::new(Suit::Heart, Rank::RankJ);
+        let joker = Card::new(Suit::BlackJoker, Rank::Rank1);
+
+        let mut hands = vec![Hand::new(); 2];
+        hands[0] = hand_of(&[spade_3, heart_j, club_4]);
+        hands[1] = hand_of(&[spade_9, club_4, joker]);
+        let mut deal = DealState::new(PlayerPos::P0, hands, cards::Deck::empty(), rules::RuleSet::default());
+
+        // First trick, leading : anything but jokers
+        assert_eq!(deal.legal_moves(PlayerPos::P0), hand_of(&[spade_3, heart_j, club_4]));
+        assert_eq!(deal.legal_moves(PlayerPos::P1), hand_of(&[spade_9, club_4]));
+
+        deal.play_card(PlayerPos::P0, spade_3).unwrap();
+        assert_eq!(deal.legal_moves(PlayerPos::P1), hand_of(&[spade_9]));
+        assert!(deal.is_legal(PlayerPos::P1, spade_9));
+        assert!(!deal.is_legal(PlayerPos::P1, club_4));
+        assert!(!deal.is_legal(PlayerPos::P1, joker));
+
+        for card in deal.hands()[1] {
+            let mut copy = deal.clone();
+            let accepted = copy.play_card(PlayerPos::P1, card).is_ok();
+            assert_eq!(accepted, deal.legal_moves(PlayerPos::P1).has(card));
+        }
+
+        // Legal moves match `is_legal` for every player during a whole deal
+        let rules = rules::RuleSet::default();
+        let (hands, stock) = crate::deal_seeded_hands([5; 32], 3, &rules);
+        let mut deal = DealState::new(PlayerPos::P0, hands, stock, rules);
+        while !deal.is_over() {
+            for pos in PlayerPos::P0.until_n(3, 3) {
+                let expected: Hand = deal.hands()[pos as usize]
+                    .iter()
+                    .filter(|card| deal.is_legal(pos, *card))
+                    .fold(Hand::new(), |moves, card| moves | card.into());
+                assert_eq!(deal.legal_moves(pos), expected);
+            }
+            let pos = deal.next_player();
+            let card = deal.legal_moves(pos).max_by_strength(points::strength).unwrap();
+            deal.play_card(pos, card).unwrap();
+        }
+
+        // Jokers are the only moves of a first trick hand without anything else,
+        // possible with small custom hands
+        let red_joker = Card::new(Suit::RedJoker, Rank::Rank1);
+        let mut hands = vec![Hand::new(); 2];
+        hands[0] = hand_of(&[spade_3, club_4]);
+        hands[1] = hand_of(&[joker, red_joker]);
+        let rules = rules::RuleSet { hand_size: 2, ..rules::RuleSet::default() };
+        let mut deal = DealState::new(PlayerPos::P0, hands, cards::Deck::empty(), rules);
+        assert_eq!(deal.legal_moves(PlayerPos::P1), hand_of(&[joker, red_joker]));
+        deal.play_card(PlayerPos::P0, spade_3).unwrap();
+        assert_eq!(deal.legal_moves(PlayerPos::P1), hand_of(&[joker, red_joker]));
+        assert!(deal.play_card(PlayerPos::P1, joker).is_ok());
+    }
+
+    #[test]
+    fn test_from_position() {
+        let rules = rules::RuleSet::default();
+        let (hands, stock) = crate::deal_seeded_hands([7; 32], 3, &rules);
+        let mut deal = DealState::new(PlayerPos::P0, hands, stock, rules);
+        for _ in 0..8 {
+            let rebuilt = DealState::from_position(
+                deal.players.clone(),
+                deal.stock.clone(),
+                deal.tricks.clone(),
+                deal.rules.clone(),
+            );
+            assert_eq!(rebuilt, deal);
+            let pos = deal.next_player();
+            let card = deal.legal_moves(pos).get_card();
+            deal.play_card(pos, card).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_serde() {
+        // Deal with 2 players after 5 plays: P1 leads the third trick
+        let fixture = include_str!("../fixtures/deal_state.json");
+        let mut deal: DealState = serde_json::from_str(fixture).unwrap();
+        assert_eq!(deal.nb_players(), 2);
+        assert_eq!(deal.next_player(), PlayerPos::P0);
+        assert_eq!(deal.stock_size(), 30);
+        assert_eq!(deal.points.tricks_won(PlayerPos::P1), 1);
+        assert_eq!(deal.current_trick().card_played(PlayerPos::P1), Some(Card::from_id(39)));
+
+        let json = serde_json::to_string(&deal).unwrap();
+        assert_eq!(serde_json::from_str::<DealState>(&json).unwrap(), deal);
+
+        let pos = deal.next_player();
+        let card = deal.legal_moves(pos).get_card();
+        assert!(deal.play_card(pos, card).is_ok());
+
+        let newer = fixture.replace("\"version\": 1", "\"version\": 2");
+        assert!(serde_json::from_str::<DealState>(&newer).is_err());
+    }
+}
@@ -1,17 +1,42 @@
-#[macro_use]
+#![cfg_attr(feature = "use_bench", feature(test))]
 
 #[cfg(feature = "use_bench")]
 extern crate test;
 
+pub mod bot;
 pub mod cards;
 pub mod deal;
 pub mod points;
 pub mod pos;
+pub mod record;
+pub mod rules;
 pub mod trick;
 
+/// Default number of players at a table
 pub const NB_PLAYERS:usize = 2;
+/// Smallest table size supported
+pub const MIN_PLAYERS:usize = 2;
+/// Largest table size supported
+pub const MAX_PLAYERS:usize = 5;
 const DEAL_SIZE:usize = 10 ;
 
+/// Random seed determining the cards of a deal
+pub type Seed = [u8; 32];
+
+/// Version of the serialized game state, increased on incompatible changes
+pub const STATE_VERSION: u32 = 1;
+
+/// Reads the version field of a serialized state.
+///
+/// Fails for states saved with another `STATE_VERSION`.
+pub fn deserialize_version<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let version = <u32 as serde::Deserialize>::deserialize(deserializer)?;
+    if version != STATE_VERSION {
+        return Err(serde::de::Error::custom(format!("unsupported state version: {}", version)));
+    }
+    Ok(version)
+}
+
 // Expose the module or their content directly? Still unsure.
 
 // pub use bid::*;
@@ -21,66 +46,130 @@ const DEAL_SIZE:usize = 10 ;
 // pub use pos::*;
 // pub use trick::*;
 
-/// Deals cards to 2 players randomly.
-pub fn deal_hands() -> ([cards::Hand; NB_PLAYERS], cards::Deck) {
-    let mut hands = [cards::Hand::new(); NB_PLAYERS];
-    let mut river = cards::Deck::new();
-    river.shuffle();
+/// Returns a new random seed.
+pub fn random_seed() -> Seed {
+    rand::random()
+}
 
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
+/// Returns the seed of the deal number `deal` (starting at 0) of a game
+/// started with `seed`.
+///
+/// Two games started with the same seed get the same deals.
+pub fn deal_seed(seed: &Seed, deal: usize) -> Seed {
+    let mut deal_seed = *seed;
+    for (byte, n) in deal_seed.iter_mut().zip((deal as u64).to_le_bytes().iter()) {
+        *byte ^= n;
+    }
+    deal_seed
+}
 
-    (hands, river)
+/// Returns the hexadecimal representation of `seed`.
+pub fn format_seed(seed: &Seed) -> String {
+    seed.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Reads a seed from its hexadecimal representation.
+pub fn parse_seed(s: &str) -> Option<Seed> {
+    let s = s.trim();
+    if s.len() != 64 || !s.is_ascii() {
+        return None;
+    }
+    let mut seed = [0; 32];
+    for (idx, byte) in seed.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[2 * idx..2 * idx + 2], 16).ok()?;
+    }
+    Some(seed)
+}
+
+/// Deals cards to `nb_players` players randomly.
+///
+/// # Panics
+/// If the rules can't be played by `nb_players` players.
+pub fn deal_hands(nb_players: usize, rules: &rules::RuleSet) -> (Vec<cards::Hand>, cards::Deck) {
+    let mut river = rules.deck();
+    river.shuffle();
+    deal_deck(river, nb_players, rules)
 }
 
 /// Deal cards deterministically.
-pub fn deal_seeded_hands(seed: [u8; 32]) -> ([cards::Hand; NB_PLAYERS], cards::Deck) {
-    let mut hands = [cards::Hand::new(); NB_PLAYERS];
-    let mut river = cards::Deck::new();
+///
+/// # Panics
+/// If the rules can't be played by `nb_players` players.
+pub fn deal_seeded_hands(seed: [u8; 32], nb_players: usize, rules: &rules::RuleSet) -> (Vec<cards::Hand>, cards::Deck) {
+    let mut river = rules.deck();
     river.shuffle_seeded(seed);
+    deal_deck(river, nb_players, rules)
+}
+
+// Deals the hands by packets from a shuffled deck, and returns what is left.
+fn deal_deck(mut river: cards::Deck, nb_players: usize, rules: &rules::RuleSet) -> (Vec<cards::Hand>, cards::Deck) {
+    if let Err(e) = rules.validate(nb_players) {
+        panic!("invalid rules: {}", e);
+    }
+    let mut hands = vec![cards::Hand::new(); nb_players];
 
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
-    river.deal_each(&mut hands, 1);
+    let mut dealt = 0;
+    while dealt < rules.hand_size {
+        let packet = rules.deal_packet.min(rules.hand_size - dealt);
+        river.deal_each(&mut hands, packet);
+        dealt += packet;
+    }
 
     (hands, river)
 }
 
 #[test]
 fn test_deals() {
-    let (hands, river) = deal_hands();
-    assert!(river.size() == 36);
+    let rules = rules::RuleSet::default();
+    for nb_players in MIN_PLAYERS..=MAX_PLAYERS {
+        let (hands, mut river) = deal_hands(nb_players, &rules);
+        assert!(hands.len() == nb_players);
+        assert!(river.len() == 54 - nb_players * DEAL_SIZE);
 
-    let mut count = [0; 54];
+        let mut count = [0; 54];
 
-    for card in river.list().iter() {
-        count[card.id() as usize] += 1;
-    }
-    for hand in hands.iter() {
-        assert!(hand.size() == DEAL_SIZE);
-        for card in hand.list().iter() {
+        while !river.is_empty() {
+            let card = river.draw();
             count[card.id() as usize] += 1;
         }
-    }
+        for hand in hands.iter() {
+            assert!(hand.size() == DEAL_SIZE);
+            for card in hand.iter() {
+                count[card.id() as usize] += 1;
+            }
+        }
 
-    for c in count.iter() {
-        assert!(*c == 1);
+        for c in count.iter() {
+            assert!(*c == 1);
+        }
     }
+}
 
+
+#[test]
+fn test_deal_packets() {
+    let rules = rules::RuleSet { hand_size: 7, deal_packet: 3, jokers: false, ..rules::RuleSet::default() };
+    let (hands, river) = deal_seeded_hands([3; 32], 3, &rules);
+    assert!(river.len() == 52 - 3 * 7);
+    for hand in hands.iter() {
+        assert!(hand.size() == 7);
+        assert!(!hand.has_any(cards::Suit::RedJoker));
+        assert!(!hand.has_any(cards::Suit::BlackJoker));
+    }
 }
 
+#[test]
+fn test_seeds() {
+    let rules = rules::RuleSet::default();
+    let seed = random_seed();
+    assert_eq!(parse_seed(&format_seed(&seed)), Some(seed));
+    assert_eq!(parse_seed("not a seed"), None);
+
+    assert_eq!(deal_seed(&seed, 0), seed);
+    assert!(deal_seed(&seed, 1) != deal_seed(&seed, 2));
+
+    let (hands, river) = deal_seeded_hands(deal_seed(&seed, 3), 2, &rules);
+    let (same_hands, same_river) = deal_seeded_hands(deal_seed(&seed, 3), 2, &rules);
+    assert_eq!(hands, same_hands);
+    assert_eq!(river, same_river);
+}
@@ -1,9 +1,27 @@
 //! Manage points and scores
 
+use serde::{Deserialize, Serialize};
+
 use super::cards;
+use super::pos;
+use super::rules;
+use super::trick;
+
+/// Points earned by the winner of a trick, in addition to its cards.
+pub const TRICK_POINTS: f32 = 1.0;
+
+/// Strength of the red joker, above any regular card
+pub const RED_JOKER_STRENGTH: i32 = 11;
+/// Strength of the black joker, the strongest card
+pub const BLACK_JOKER_STRENGTH: i32 = 12;
 
 /// Returns the strength of `card`
 pub fn strength(card: cards::Card) -> i32 {
+    match card.suit() {
+        cards::Suit::RedJoker => return RED_JOKER_STRENGTH,
+        cards::Suit::BlackJoker => return BLACK_JOKER_STRENGTH,
+        _ => (),
+    }
     let rank = card.rank();
         match rank {
             cards::Rank::Rank1  => 1,
@@ -21,3 +39,123 @@ pub fn strength(card: cards::Card) -> i32 {
             cards::Rank::RankK  => 3,
         }
 }
+
+/// Returns the points `card` is worth when won in a trick.
+///
+/// Only the face cards count: 1 for a jack, 2 for a queen and 3 for a king.
+/// Each joker is worth the `joker_points` of `scoring`.
+pub fn score(card: cards::Card, scoring: &rules::ScoringRules) -> f32 {
+    if card.is_joker() {
+        return scoring.joker_points;
+    }
+    match card.rank() {
+        cards::Rank::RankJ => 1.0,
+        cards::Rank::RankQ => 2.0,
+        cards::Rank::RankK => 3.0,
+        _ => 0.0,
+    }
+}
+
+/// Returns the points of all the cards in `hand`.
+pub fn hand_score(hand: cards::Hand, scoring: &rules::ScoringRules) -> f32 {
+    hand.iter().map(|card| score(card, scoring)).sum()
+}
+
+/// Cards and tricks won by each player during a deal.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Tally {
+    cards: Vec<cards::Hand>,
+    tricks: Vec<usize>,
+}
+
+impl Tally {
+    /// Creates an empty tally for `nb_players` players, before the first trick.
+    pub fn new(nb_players: usize) -> Self {
+        Tally {
+            cards: vec![cards::Hand::new(); nb_players],
+            tricks: vec![0; nb_players],
+        }
+    }
+
+    /// Gives the cards of a finished trick to its winner.
+    pub fn add_trick(&mut self, trick: &trick::Trick) {
+        let winner = trick.winner as usize;
+        for card in trick.cards.iter().flatten() {
+            self.cards[winner].add(*card);
+        }
+        self.tricks[winner] += 1;
+    }
+
+    /// Returns the cards won by `pos`.
+    pub fn won_cards(&self, pos: pos::PlayerPos) -> cards::Hand {
+        self.cards[pos as usize]
+    }
+
+    /// Returns the number of tricks won by `pos`.
+    pub fn tricks_won(&self, pos: pos::PlayerPos) -> usize {
+        self.tricks[pos as usize]
+    }
+
+    /// Returns the points earned so far by each player, counted with `rules`.
+    pub fn points(&self, rules: &rules::RuleSet) -> Vec<f32> {
+        self.cards
+            .iter()
+            .zip(self.tricks.iter())
+            .map(|(cards, tricks)| {
+                let cards_points: f32 = cards.iter().map(|c| rules.card_points(c)).sum();
+                cards_points + rules.scoring.trick_points * *tricks as f32
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::{Card, Rank, Suit};
+    use crate::pos::PlayerPos;
+    use crate::rules::RuleSet;
+
+    #[test]
+    fn test_tally() {
+        let rules = RuleSet::default();
+        let mut tally = Tally::new(2);
+
+        let mut trick = trick::Trick::new(PlayerPos::P0, 2);
+        trick.play_card(PlayerPos::P0, Card::new(Suit::Heart, Rank::RankK), &rules);
+        trick.play_card(PlayerPos::P1, Card::new(Suit::Heart, Rank::Rank7), &rules);
+        tally.add_trick(&trick);
+
+        let mut trick = trick::Trick::new(PlayerPos::P1, 2);
+        trick.play_card(PlayerPos::P1, Card::new(Suit::Club, Rank::Rank2), &rules);
+        trick.play_card(PlayerPos::P0, Card::new(Suit::Club, Rank::RankQ), &rules);
+        tally.add_trick(&trick);
+
+        assert_eq!(tally.tricks_won(PlayerPos::P0), 0);
+        assert_eq!(tally.tricks_won(PlayerPos::P1), 2);
+        assert_eq!(tally.won_cards(PlayerPos::P1).size(), 4);
+        assert_eq!(tally.points(&rules), vec![0.0, 7.0]);
+
+        let rules = RuleSet { scoring: rules::ScoringRules { trick_points: 0.0, ..Default::default() }, ..rules };
+        assert_eq!(tally.points(&rules), vec![0.0, 5.0]);
+    }
+
+    #[test]
+    fn test_jokers() {
+        let red = Card::new(Suit::RedJoker, Rank::Rank1);
+        let black = Card::new(Suit::BlackJoker, Rank::Rank1);
+        let ten = Card::new(Suit::Spade, Rank::Rank10);
+        assert!(strength(red) > strength(ten));
+        assert!(strength(black) > strength(red));
+        let scoring = rules::ScoringRules::default();
+        assert_eq!(score(red, &scoring), 5.0);
+        assert_eq!(score(black, &scoring), 5.0);
+        assert_eq!(score(ten, &scoring), 0.0);
+
+        let scoring = rules::ScoringRules { joker_points: 10.0, ..scoring };
+        assert_eq!(score(red, &scoring), 10.0);
+        let mut hand = cards::Hand::new();
+        hand.add(red).add(Card::new(Suit::Heart, Rank::RankQ));
+        assert_eq!(hand_score(hand, &scoring), 12.0);
+    }
+}
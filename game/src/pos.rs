@@ -3,32 +3,33 @@
 use serde::{Deserialize, Serialize};
 
 /// A position in the table
-#[derive(PartialEq,Clone,Copy,Debug,Serialize,Deserialize)]
+#[derive(PartialEq,Clone,Copy,Debug,Serialize,Deserialize,Eq,Hash)]
 pub enum PlayerPos {
     /// Player 0
     P0,
     /// Player 1
     P1,
-    // /// Player 2
-    // P2,
-    // /// Player 3
-    // P3,
-    // /// Player 4
-    // P4,
+    /// Player 2
+    P2,
+    /// Player 3
+    P3,
+    /// Player 4
+    P4,
 }
 
-pub const POSITIONS_LIST: [PlayerPos;2] = [
+pub const POSITIONS_LIST: [PlayerPos;5] = [
     PlayerPos::P0,
     PlayerPos::P1,
-    // PlayerPos::P2,
-    // PlayerPos::P3,
-    // PlayerPos::P4,
+    PlayerPos::P2,
+    PlayerPos::P3,
+    PlayerPos::P4,
 ];
 
 /// Iterates on players
 pub struct PlayerIterator {
     current: PlayerPos,
     remaining: usize,
+    count: usize,
 }
 
 impl Iterator for PlayerIterator {
@@ -40,7 +41,7 @@ impl Iterator for PlayerIterator {
         }
 
         let r = self.current;
-        self.current = self.current.next();
+        self.current = self.current.next(self.count);
         self.remaining -= 1;
         Some(r)
     }
@@ -54,9 +55,9 @@ impl PlayerPos {
         match n {
             0 => PlayerPos::P0,
             1 => PlayerPos::P1,
-            // 2 => PlayerPos::P2,
-            // 3 => PlayerPos::P3,
-            // 4 => PlayerPos::P4,
+            2 => PlayerPos::P2,
+            3 => PlayerPos::P3,
+            4 => PlayerPos::P4,
             other => panic!("invalid pos: {}", other),
         }
     }
@@ -67,89 +68,79 @@ impl PlayerPos {
         match self {
             PlayerPos::P0 => 0,
             PlayerPos::P1 => 1,
-            // PlayerPos::P2 => 2,
-            // PlayerPos::P3 => 3,
-            // PlayerPos::P4 => 4,
+            PlayerPos::P2 => 2,
+            PlayerPos::P3 => 3,
+            PlayerPos::P4 => 4,
         }
     }
 
-    /// Returns the next player in line
-    pub fn next(self) -> PlayerPos {
-        match self {
-            PlayerPos::P0 => PlayerPos::P1,
-            PlayerPos::P1 => PlayerPos::P0,
-            // PlayerPos::P2 => PlayerPos::P3,
-            // PlayerPos::P3 => PlayerPos::P4,
-            // PlayerPos::P4 => PlayerPos::P0,
-        }
+    /// Returns the next player in line, on a table of `count` players
+    pub fn next(self, count: usize) -> PlayerPos {
+        self.next_n(1, count)
     }
 
-    /// Returns the player `n` seats further
-    pub fn next_n(self, n: usize) -> PlayerPos {
-        if n == 0 {
-            self
-        } else {
-            PlayerPos::from_n((self as usize + n) % 2)
-        }
+    /// Returns the player `n` seats further, on a table of `count` players
+    pub fn next_n(self, n: usize, count: usize) -> PlayerPos {
+        PlayerPos::from_n((self.to_n() + n) % count)
     }
 
-    /// Returns the previous player.
-    pub fn prev(self) -> PlayerPos {
-        match self {
-            PlayerPos::P0 => PlayerPos::P1,
-            PlayerPos::P1 => PlayerPos::P0,
-            // PlayerPos::P2 => PlayerPos::P1,
-            // PlayerPos::P3 => PlayerPos::P2,
-            // PlayerPos::P4 => PlayerPos::P3,
-        }
+    /// Returns the previous player, on a table of `count` players.
+    pub fn prev(self, count: usize) -> PlayerPos {
+        self.next_n(count - 1, count)
     }
 
-    /// Returns an iterator that iterates on `n` players, including this one.
-    pub fn until_n(self, n: usize) -> PlayerIterator {
+    /// Returns an iterator that iterates on `n` players, including this one,
+    /// on a table of `count` players.
+    pub fn until_n(self, n: usize, count: usize) -> PlayerIterator {
         PlayerIterator {
             current: self,
             remaining: n,
+            count,
         }
     }
 
-    /// Returns the number of turns after `self` to reach `other`.
-    pub fn distance_until(self, other: PlayerPos) -> usize {
-        (1 + other as usize - self as usize) % 2 + 1
-        // (4 + other as usize - self as usize) % 5 + 1
+    /// Returns the number of turns after `self` to reach `other`, on a table
+    /// of `count` players.
+    ///
+    /// A full round is needed to come back to `self`.
+    pub fn distance_until(self, other: PlayerPos, count: usize) -> usize {
+        (count - 1 + other.to_n() - self.to_n()) % count + 1
     }
 
     /// Returns an iterator until the given player (`self` included, `other` excluded)
-    pub fn until(self, other: PlayerPos) -> PlayerIterator {
-        let d = self.distance_until(other);
-        self.until_n(d)
+    pub fn until(self, other: PlayerPos, count: usize) -> PlayerIterator {
+        let d = self.distance_until(other, count);
+        self.until_n(d, count)
     }
 }
 
-// #[cfg(test)]
-// mod tests {
-//
-//     use super::*;
-//
-//     #[test]
-//     fn test_pos() {
-//         let mut count = [0; 5];
-//         for i in 0..5 {
-//             for pos in PlayerPos::from_n(i).until(PlayerPos::from_n(0)) {
-//                 count[pos as usize] += 1;
-//             }
-//             for pos in PlayerPos::from_n(0).until(PlayerPos::from_n(i)) {
-//                 count[pos as usize] += 1;
-//             }
-//         }
-//
-//         for c in count.iter() {
-//             assert!(*c == 6);
-//         }
-//
-//         for i in 0..5 {
-//             assert!(PlayerPos::from_n(i).next() == PlayerPos::from_n((i + 1) % 5));
-//             assert!(PlayerPos::from_n(i) == PlayerPos::from_n((i + 1) % 5).prev());
-//             assert!(PlayerPos::from_n(i).next().prev() == PlayerPos::from_n(i));
-//         }
-//     }
-// }
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_pos() {
+        for count in 2..6 {
+            let mut seen = [0; 5];
+            for i in 0..count {
+                for pos in PlayerPos::from_n(i).until(PlayerPos::from_n(0), count) {
+                    seen[pos as usize] += 1;
+                }
+                for pos in PlayerPos::from_n(0).until(PlayerPos::from_n(i), count) {
+                    seen[pos as usize] += 1;
+                }
+            }
+
+            for c in seen[..count].iter() {
+                assert!(*c == count + 1);
+            }
+
+            for i in 0..count {
+                assert!(PlayerPos::from_n(i).next(count) == PlayerPos::from_n((i + 1) % count));
+                assert!(PlayerPos::from_n(i) == PlayerPos::from_n((i + 1) % count).prev(count));
+                assert!(PlayerPos::from_n(i).next(count).prev(count) == PlayerPos::from_n(i));
+            }
+        }
+    }
+}
@@ -0,0 +1,379 @@
+//! Record of a played game, to archive, share and replay it.
+//!
+//! A record holds everything needed to rebuild the game: the rules, the seed
+//! the deals were made from, the seats and the ordered plays of each deal.
+//!
+//! It is serialized as JSON with serde, or as text, similar to PGN for chess:
+//!
+//! ```text
+//! [Seed "5f0e...c2"]
+//! [Players "2"]
+//! [P0 "alice"]
+//! [P1 "bob"]
+//! [HandSize "10"]
+//! ...
+//!
+//! [Deal "P0"]
+//! P0 7♦ P1 K♦
+//! P1 3♥ P0 9♥
+//! ```
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use super::cards;
+use super::deal;
+use super::pos;
+use super::rules;
+use super::{deal_seed, deal_seeded_hands, format_seed, parse_seed, Seed};
+
+/// A card played by a player.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct PlayRecord {
+    pub pos: pos::PlayerPos,
+    pub card: cards::Card,
+}
+
+/// The plays of a deal.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct DealRecord {
+    /// Player starting the deal
+    pub first: pos::PlayerPos,
+    pub plays: Vec<PlayRecord>,
+}
+
+impl DealRecord {
+    pub fn new(first: pos::PlayerPos) -> Self {
+        DealRecord {
+            first,
+            plays: vec![],
+        }
+    }
+}
+
+/// Record of a whole game.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub rules: rules::RuleSet,
+    /// Seed of the game, each deal uses `deal_seed(seed, deal)`
+    pub seed: Seed,
+    /// Nickname of the player at each seat
+    pub seats: Vec<String>,
+    pub deals: Vec<DealRecord>,
+}
+
+/// Error found while reading or replaying a record.
+#[derive(PartialEq, Debug)]
+pub enum RecordError {
+    /// A line of the text form could not be read
+    Syntax { line: usize, message: String },
+    /// The rules can't be played by the seats of the record
+    Rules(rules::RulesError),
+    /// A play of the record is not allowed
+    IllegalPlay {
+        deal: usize,
+        play: usize,
+        error: deal::PlayError,
+    },
+    /// A play was recorded after the end of its deal
+    DealOver { deal: usize, play: usize },
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            RecordError::Rules(error) => write!(f, "rules: {}", error),
+            RecordError::IllegalPlay { deal, play, error } => {
+                write!(f, "deal {}, play {}: {}", deal + 1, play + 1, error)
+            }
+            RecordError::DealOver { deal, play } => {
+                write!(f, "deal {}, play {}: the deal is already over", deal + 1, play + 1)
+            }
+        }
+    }
+}
+
+impl GameRecord {
+    /// Creates a record of a game without any play yet.
+    pub fn new(rules: rules::RuleSet, seed: Seed, seats: Vec<String>) -> Self {
+        GameRecord {
+            rules,
+            seed,
+            seats,
+            deals: vec![],
+        }
+    }
+
+    pub fn nb_players(&self) -> usize {
+        self.seats.len()
+    }
+
+    /// Replays every deal of the record, checking that each play is legal.
+    ///
+    /// Returns the state of each deal after its last recorded play.
+    pub fn replay(&self) -> Result<Vec<deal::DealState>, RecordError> {
+        let nb_players = self.nb_players();
+        self.rules.validate(nb_players).map_err(RecordError::Rules)?;
+
+        let mut states = vec![];
+        for (deal_idx, deal_record) in self.deals.iter().enumerate() {
+            let (hands, stock) = deal_seeded_hands(deal_seed(&self.seed, deal_idx), nb_players, &self.rules);
+            let mut state = deal::DealState::new(deal_record.first, hands, stock, self.rules.clone());
+            for (play_idx, play) in deal_record.plays.iter().enumerate() {
+                if state.is_over() {
+                    return Err(RecordError::DealOver { deal: deal_idx, play: play_idx });
+                }
+                state.play_card(play.pos, play.card).map_err(|error| RecordError::IllegalPlay {
+                    deal: deal_idx,
+                    play: play_idx,
+                    error,
+                })?;
+            }
+            states.push(state);
+        }
+        Ok(states)
+    }
+
+    /// Returns the points of each finished deal.
+    pub fn scores(&self) -> Result<Vec<Vec<f32>>, RecordError> {
+        Ok(self.replay()?
+            .iter()
+            .filter(|state| state.is_over())
+            .map(|state| state.points())
+            .collect())
+    }
+
+    /// Returns the text form of the record.
+    pub fn to_text(&self) -> String {
+        let scoring = &self.rules.scoring;
+        let mut s = format!("[Seed \"{}\"]\n", format_seed(&self.seed));
+        s += &format!("[Players \"{}\"]\n", self.nb_players());
+        for (idx, nickname) in self.seats.iter().enumerate() {
+            s += &format!("[{:?} \"{}\"]\n", pos::PlayerPos::from_n(idx), nickname);
+        }
+        s += &format!("[HandSize \"{}\"]\n", self.rules.hand_size);
+        s += &format!("[DealPacket \"{}\"]\n", self.rules.deal_packet);
+        s += &format!("[Jokers \"{}\"]\n", self.rules.jokers);
+        s += &format!("[FaceStrength \"{}\"]\n", face_strength_name(self.rules.face_strength));
+        s += &format!("[TrickPoints \"{}\"]\n", scoring.trick_points);
+        s += &format!("[JokerPoints \"{}\"]\n", scoring.joker_points);
+        s += &format!("[LastTrickBonus \"{}\"]\n", scoring.last_trick_bonus);
+
+        for deal_record in self.deals.iter() {
+            s += &format!("\n[Deal \"{:?}\"]\n", deal_record.first);
+            // One line per trick
+            for trick in deal_record.plays.chunks(self.nb_players().max(1)) {
+                let plays: Vec<String> = trick
+                    .iter()
+                    .map(|play| format!("{:?} {}", play.pos, play.card))
+                    .collect();
+                s += &plays.join(" ");
+                s += "\n";
+            }
+        }
+        s
+    }
+
+    /// Reads a record from its text form.
+    ///
+    /// Plays are not checked, use `replay` for this.
+    pub fn from_text(text: &str) -> Result<Self, RecordError> {
+        let mut record = GameRecord::new(rules::RuleSet::default(), [0; 32], vec![]);
+        let mut nb_players = None;
+        let mut seed = None;
+
+        for (idx, line) in text.lines().enumerate() {
+            let line_nb = idx + 1;
+            let syntax = |message: &str| RecordError::Syntax {
+                line: line_nb,
+                message: message.to_owned(),
+            };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('[') {
+                let (name, value) = parse_tag(line).ok_or_else(|| syntax("invalid tag"))?;
+                let invalid_value = || syntax(&format!("invalid value for {}", name));
+                let rules = &mut record.rules;
+                match name {
+                    "Seed" => seed = Some(parse_seed(value).ok_or_else(invalid_value)?),
+                    "Players" => nb_players = Some(parse_value::<usize>(value).ok_or_else(invalid_value)?),
+                    "HandSize" => rules.hand_size = parse_value(value).ok_or_else(invalid_value)?,
+                    "DealPacket" => rules.deal_packet = parse_value(value).ok_or_else(invalid_value)?,
+                    "Jokers" => rules.jokers = parse_value(value).ok_or_else(invalid_value)?,
+                    "FaceStrength" => {
+                        rules.face_strength = parse_face_strength(value).ok_or_else(invalid_value)?
+                    }
+                    "TrickPoints" => rules.scoring.trick_points = parse_value(value).ok_or_else(invalid_value)?,
+                    "JokerPoints" => rules.scoring.joker_points = parse_value(value).ok_or_else(invalid_value)?,
+                    "LastTrickBonus" => {
+                        rules.scoring.last_trick_bonus = parse_value(value).ok_or_else(invalid_value)?
+                    }
+                    "Deal" => {
+                        let first = parse_pos(value).ok_or_else(invalid_value)?;
+                        record.deals.push(DealRecord::new(first));
+                    }
+                    seat => {
+                        let pos = parse_pos(seat).ok_or_else(|| syntax(&format!("unknown tag {}", seat)))?;
+                        if pos.to_n() != record.seats.len() {
+                            return Err(syntax("seats must be listed in order"));
+                        }
+                        record.seats.push(value.to_owned());
+                    }
+                }
+            } else {
+                let deal_record = record.deals.last_mut().ok_or_else(|| syntax("play before any deal"))?;
+                let tokens: Vec<&str> = line.split_whitespace().collect();
+                for play in tokens.chunks(2) {
+                    if play.len() != 2 {
+                        return Err(syntax("a play needs a position and a card"));
+                    }
+                    let pos = parse_pos(play[0]).ok_or_else(|| syntax(&format!("invalid position {}", play[0])))?;
+                    let card = play[1].parse().map_err(|e: cards::ParseError| syntax(&e.to_string()))?;
+                    deal_record.plays.push(PlayRecord { pos, card });
+                }
+            }
+        }
+
+        record.seed = seed.ok_or_else(|| RecordError::Syntax {
+            line: 0,
+            message: "missing seed".to_owned(),
+        })?;
+        if nb_players != Some(record.seats.len()) {
+            return Err(RecordError::Syntax {
+                line: 0,
+                message: "seats don't match the number of players".to_owned(),
+            });
+        }
+        Ok(record)
+    }
+}
+
+/// Splits a `[Name "value"]` line.
+fn parse_tag(line: &str) -> Option<(&str, &str)> {
+    let inner = between(line, '[', ']')?;
+    let quote = inner.find('"')?;
+    let name = inner[..quote].trim();
+    let value = between(&inner[quote..], '"', '"')?;
+    if name.is_empty() {
+        None
+    } else {
+        Some((name, value))
+    }
+}
+
+// Returns `s` without its `open` and `close` delimiters, if it has both
+fn between(s: &str, open: char, close: char) -> Option<&str> {
+    if s.len() < open.len_utf8() + close.len_utf8() || !s.starts_with(open) || !s.ends_with(close) {
+        return None;
+    }
+    Some(&s[open.len_utf8()..s.len() - close.len_utf8()])
+}
+
+fn parse_value<T: FromStr>(value: &str) -> Option<T> {
+    value.parse().ok()
+}
+
+fn face_strength_name(face_strength: rules::FaceStrength) -> &'static str {
+    match face_strength {
+        rules::FaceStrength::Low => "low",
+        rules::FaceStrength::High => "high",
+    }
+}
+
+fn parse_face_strength(value: &str) -> Option<rules::FaceStrength> {
+    match value {
+        "low" => Some(rules::FaceStrength::Low),
+        "high" => Some(rules::FaceStrength::High),
+        _ => None,
+    }
+}
+
+fn parse_pos(value: &str) -> Option<pos::PlayerPos> {
+    pos::POSITIONS_LIST.iter().copied().find(|p| format!("{:?}", p) == value)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Plays the first legal card until every deal is over.
+    fn play_record(nb_deals: usize) -> GameRecord {
+        let seats = vec!["alice".to_owned(), "bob".to_owned(), "carol".to_owned()];
+        let mut record = GameRecord::new(rules::RuleSet::default(), [3; 32], seats);
+        let mut first = pos::PlayerPos::P0;
+        for deal_idx in 0..nb_deals {
+            let (hands, stock) = deal_seeded_hands(deal_seed(&record.seed, deal_idx), 3, &record.rules);
+            let mut state = deal::DealState::new(first, hands, stock, record.rules.clone());
+            let mut deal_record = DealRecord::new(first);
+            while !state.is_over() {
+                let pos = state.next_player();
+                let card = state.legal_moves(pos).list()[0];
+                state.play_card(pos, card).unwrap();
+                deal_record.plays.push(PlayRecord { pos, card });
+            }
+            record.deals.push(deal_record);
+            first = first.next(3);
+        }
+        record
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        let record = play_record(2);
+        let text = record.to_text();
+        assert_eq!(GameRecord::from_text(&text), Ok(record.clone()));
+
+        let scores = record.scores().unwrap();
+        assert_eq!(scores.len(), 2);
+        for points in scores.iter() {
+            assert_eq!(points.len(), 3);
+        }
+
+        assert!(GameRecord::from_text("[Players \"2\"]").is_err());
+        assert_eq!(
+            GameRecord::from_text("[Seed \"00\"]"),
+            Err(RecordError::Syntax { line: 1, message: "invalid value for Seed".to_owned() })
+        );
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let record = play_record(1);
+        let json = serde_json::to_string(&record).unwrap();
+        assert_eq!(serde_json::from_str::<GameRecord>(&json).unwrap(), record);
+    }
+
+    #[test]
+    fn test_replay_checks_plays() {
+        let mut record = play_record(1);
+        record.deals[0].plays.swap(0, 1);
+        match record.replay() {
+            Err(RecordError::IllegalPlay { deal: 0, play: 0, error: deal::PlayError::TurnError }) => (),
+            other => panic!("unexpected replay: {:?}", other.map(|states| states.len())),
+        }
+
+        let mut record = play_record(1);
+        let last = *record.deals[0].plays.last().unwrap();
+        record.deals[0].plays.push(last);
+        assert_eq!(
+            record.replay().err(),
+            Some(RecordError::DealOver { deal: 0, play: record.deals[0].plays.len() - 1 })
+        );
+    }
+
+    #[test]
+    fn test_parse_tag() {
+        assert_eq!(parse_tag(r#"[Seat1 "alice"]"#), Some(("Seat1", "alice")));
+        assert_eq!(parse_tag(r#"[Event ""]"#), Some(("Event", "")));
+        assert_eq!(parse_tag(r#"[Seat1 "]"#), None);
+        assert_eq!(parse_tag(r#"[ "alice"]"#), None);
+        assert_eq!(parse_tag(r#"Seat1 "alice""#), None);
+        assert_eq!(parse_tag("["), None);
+    }
+}
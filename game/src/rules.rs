@@ -0,0 +1,167 @@
+//! Rule variants of the game.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::cards;
+use super::points;
+
+/// Strength of the face cards in a trick.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaceStrength {
+    /// Jacks, queens and kings are as strong as 1, 2 and 3.
+    Low,
+    /// Jacks, queens and kings are stronger than 10.
+    High,
+}
+
+/// How the points of a deal are counted.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct ScoringRules {
+    /// Points for each trick won, in addition to its cards
+    pub trick_points: f32,
+    /// Points of a joker won in a trick
+    pub joker_points: f32,
+    /// Bonus for the winner of the last trick of the deal
+    pub last_trick_bonus: f32,
+}
+
+impl Default for ScoringRules {
+    fn default() -> Self {
+        ScoringRules {
+            trick_points: points::TRICK_POINTS,
+            joker_points: 5.0,
+            last_trick_bonus: 0.0,
+        }
+    }
+}
+
+/// Set of rules a deal is played with.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct RuleSet {
+    /// Number of cards dealt to each player
+    pub hand_size: usize,
+    /// Number of cards given to a player at once when dealing
+    pub deal_packet: usize,
+    /// Are the two jokers in the deck
+    pub jokers: bool,
+    /// Strength of the face cards
+    pub face_strength: FaceStrength,
+    pub scoring: ScoringRules,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet {
+            hand_size: super::DEAL_SIZE,
+            deal_packet: 1,
+            jokers: true,
+            face_strength: FaceStrength::Low,
+            scoring: ScoringRules::default(),
+        }
+    }
+}
+
+/// Error for rules which can't be played.
+#[derive(PartialEq, Debug)]
+pub enum RulesError {
+    /// The number of players is not supported
+    PlayerCount,
+    /// Hands or packets would be empty
+    EmptyHand,
+    /// The deck does not have enough cards for all the hands
+    NotEnoughCards,
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RulesError::PlayerCount => write!(f, "invalid number of players"),
+            RulesError::EmptyHand => write!(f, "hands and packets can't be empty"),
+            RulesError::NotEnoughCards => write!(f, "not enough cards for every player"),
+        }
+    }
+}
+
+impl RuleSet {
+    /// Checks that a deal can be played by `nb_players` players with these rules.
+    pub fn validate(&self, nb_players: usize) -> Result<(), RulesError> {
+        if !(super::MIN_PLAYERS..=super::MAX_PLAYERS).contains(&nb_players) {
+            return Err(RulesError::PlayerCount);
+        }
+        if self.hand_size == 0 || self.deal_packet == 0 {
+            return Err(RulesError::EmptyHand);
+        }
+        if self.deck().len() < self.hand_size * nb_players {
+            return Err(RulesError::NotEnoughCards);
+        }
+        Ok(())
+    }
+
+    /// Returns a full, sorted deck for these rules.
+    pub fn deck(&self) -> cards::Deck {
+        if self.jokers {
+            cards::Deck::new()
+        } else {
+            cards::Deck::without_jokers()
+        }
+    }
+
+    /// Returns the strength of `card` in a trick.
+    pub fn strength(&self, card: cards::Card) -> i32 {
+        if self.face_strength == FaceStrength::Low {
+            return points::strength(card);
+        }
+        match card.rank() {
+            // Jokers stay above the kings
+            _ if card.is_joker() => points::strength(card) + 3,
+            cards::Rank::RankJ => 11,
+            cards::Rank::RankQ => 12,
+            cards::Rank::RankK => 13,
+            _ => points::strength(card),
+        }
+    }
+
+    /// Returns the points `card` is worth when won in a trick.
+    pub fn card_points(&self, card: cards::Card) -> f32 {
+        points::score(card, &self.scoring)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::{Card, Rank, Suit};
+
+    #[test]
+    fn test_validate() {
+        let rules = RuleSet::default();
+        assert_eq!(rules.validate(2), Ok(()));
+        assert_eq!(rules.validate(5), Ok(()));
+        assert_eq!(rules.validate(6), Err(RulesError::PlayerCount));
+
+        let rules = RuleSet { hand_size: 11, jokers: false, ..RuleSet::default() };
+        assert_eq!(rules.validate(4), Ok(()));
+        assert_eq!(rules.validate(5), Err(RulesError::NotEnoughCards));
+
+        let rules = RuleSet { deal_packet: 0, ..RuleSet::default() };
+        assert_eq!(rules.validate(2), Err(RulesError::EmptyHand));
+    }
+
+    #[test]
+    fn test_strength() {
+        let king = Card::new(Suit::Club, Rank::RankK);
+        let ten = Card::new(Suit::Club, Rank::Rank10);
+        let joker = Card::new(Suit::RedJoker, Rank::Rank1);
+
+        let rules = RuleSet::default();
+        assert!(rules.strength(king) < rules.strength(ten));
+        assert!(rules.strength(joker) > rules.strength(ten));
+
+        let rules = RuleSet { face_strength: FaceStrength::High, ..RuleSet::default() };
+        assert!(rules.strength(king) > rules.strength(ten));
+        assert!(rules.strength(joker) > rules.strength(king));
+    }
+}
@@ -4,40 +4,43 @@ use serde::{Serialize, Deserialize};
 
 use super::cards;
 use super::pos;
+use super::rules;
 
 /// The current cards on the table.
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub struct Trick {
-    /// Cards currently on the table (they are `None` until played).
-    pub cards: [Option<cards::Card>; super::NB_PLAYERS],
+    /// Cards currently on the table, one slot per player (they are `None` until played).
+    pub cards: Vec<Option<cards::Card>>,
     /// First player in this trick.
     pub first: pos::PlayerPos,
     /// Current winner of the trick (updated after each card played).
     pub winner: pos::PlayerPos,
 }
 
+impl Default for Trick {
+    /// Creates a default trick
+    fn default() -> Self {
+        Trick::new(pos::PlayerPos::P0, super::NB_PLAYERS)
+    }
+}
+
 impl Trick {
-    /// Creates a new, empty trick.
-    pub fn new(first: pos::PlayerPos) -> Self {
+    /// Creates a new, empty trick for `nb_players` players.
+    pub fn new(first: pos::PlayerPos, nb_players: usize) -> Self {
         Trick {
             first,
             winner: first,
-            cards: [None; super::NB_PLAYERS],
+            cards: vec![None; nb_players],
         }
     }
 
-    /// Creates a default trick
-    pub fn default() -> Self {
-        let default = pos::PlayerPos::P0;
-        Trick {
-            first: default,
-            winner: default,
-            cards: [None; super::NB_PLAYERS],
-        }
+    /// Returns the number of players taking part in this trick.
+    pub fn nb_players(&self) -> usize {
+        self.cards.len()
     }
 
     pub fn card_played(&self, pos: pos::PlayerPos) -> Option<cards::Card> {
-        self.cards[pos.to_n()]
+        self.cards.get(pos.to_n()).copied().flatten()
         // let first_pos = self.first.to_n();
         // let player_pos = pos.to_n();
         // let trick_pos = if player_pos < first_pos {
@@ -50,7 +53,7 @@ impl Trick {
 
     /// Returns the player who played a card
     pub fn player_played(&self, card: cards::Card) -> Option<pos::PlayerPos> {
-        self.cards.iter().position(|c| c == &Some(card)).map(|idx| pos::PlayerPos::from_n(idx))
+        self.cards.iter().position(|c| c == &Some(card)).map(pos::PlayerPos::from_n)
     }
 
     /// Returns `true` if `self` contains `card`.
@@ -60,27 +63,58 @@ impl Trick {
 
     /// Plays a card.
     ///
-    /// Updates the winner.
+    /// Updates the winner, according to the strength of cards in `rules`.
     ///
     /// Returns `true` if this completes the trick.
     pub fn play_card(
         &mut self,
         player: pos::PlayerPos,
         card: cards::Card,
+        rules: &rules::RuleSet,
     ) -> bool {
         self.cards[player as usize] = Some(card);
         if player == self.first {
             return false;
         }
 
-        player == self.first.prev()
+        if let Some(winning) = self.cards[self.winner as usize] {
+            if beats(card, winning, rules) {
+                self.winner = player;
+            }
+        }
+
+        player == self.first.prev(self.nb_players())
     }
 
-    /// Returns the starting suit for this trick.
+    /// Returns the suit to follow in this trick.
     ///
-    /// Returns `None` if the trick hasn't started yet.
+    /// This is the suit of the first regular card played: when a joker is led,
+    /// the next card sets the suit.
+    ///
+    /// Returns `None` if no regular card has been played yet.
     pub fn suit(&self) -> Option<cards::Suit> {
-        self.cards[self.first as usize].map(|c| c.suit())
+        self.first
+            .until_n(self.nb_players(), self.nb_players())
+            .filter_map(|pos| self.cards[pos as usize])
+            .find(|card| !card.is_joker())
+            .map(|card| card.suit())
+    }
+}
+
+/// Returns `true` if `card` takes the trick from the currently winning card.
+///
+/// The winning card always is either of the suit to follow or a joker: jokers
+/// beat any regular card, and are ranked between themselves by strength.
+/// Otherwise only a strictly stronger card of the same suit wins; ties stay
+/// with the first one.
+fn beats(card: cards::Card, winning: cards::Card, rules: &rules::RuleSet) -> bool {
+    match (card.is_joker(), winning.is_joker()) {
+        (true, false) => true,
+        (false, true) => false,
+        (true, true) => rules.strength(card) > rules.strength(winning),
+        (false, false) => {
+            card.suit() == winning.suit() && rules.strength(card) > rules.strength(winning)
+        }
     }
 }
 
@@ -88,27 +122,131 @@ impl Trick {
 mod tests {
     use super::*;
     use crate::{cards, pos};
+    use crate::rules::{FaceStrength, RuleSet};
 
     #[test]
     fn test_play_card() {
+        let rules = RuleSet::default();
         let mut trick = Trick::default();
         trick.play_card(
             pos::PlayerPos::P0,
-            cards::Card::new(cards::Suit::Club, cards::Rank::Rank5)
+            cards::Card::new(cards::Suit::Club, cards::Rank::Rank5),
+            &rules
         );
         assert_eq!( trick.winner, pos::PlayerPos::P0);
 
         //Higher card
         trick.play_card(
             pos::PlayerPos::P1,
-            cards::Card::new(cards::Suit::Club, cards::Rank::Rank8)
+            cards::Card::new(cards::Suit::Club, cards::Rank::Rank8),
+            &rules
+        );
+        assert_eq!( trick.winner, pos::PlayerPos::P1);
+
+        //Higher rank but wrong color
+        let mut trick = Trick::default();
+        trick.play_card(
+            pos::PlayerPos::P0,
+            cards::Card::new(cards::Suit::Club, cards::Rank::Rank5),
+            &rules
+        );
+        let over = trick.play_card(
+            pos::PlayerPos::P1,
+            cards::Card::new(cards::Suit::Heart, cards::Rank::Rank10),
+            &rules
+        );
+        assert!(over);
+        assert_eq!( trick.winner, pos::PlayerPos::P0);
+
+        //Same strength : the first card played wins
+        let mut trick = Trick::new(pos::PlayerPos::P1, 2);
+        trick.play_card(
+            pos::PlayerPos::P1,
+            cards::Card::new(cards::Suit::Spade, cards::Rank::Rank2),
+            &rules
+        );
+        trick.play_card(
+            pos::PlayerPos::P0,
+            cards::Card::new(cards::Suit::Spade, cards::Rank::RankQ),
+            &rules
         );
         assert_eq!( trick.winner, pos::PlayerPos::P1);
+    }
 
-        //Higher rank bug wrong color
+    #[test]
+    fn test_jokers() {
+        let rules = RuleSet::default();
+        let mut trick = Trick::default();
+        trick.play_card(
+            pos::PlayerPos::P0,
+            cards::Card::new(cards::Suit::Club, cards::Rank::Rank10),
+            &rules
+        );
+        trick.play_card(
+            pos::PlayerPos::P1,
+            cards::Card::new(cards::Suit::RedJoker, cards::Rank::Rank1),
+            &rules
+        );
+        assert_eq!( trick.winner, pos::PlayerPos::P1);
+
+        let mut trick = Trick::default();
+        trick.play_card(
+            pos::PlayerPos::P0,
+            cards::Card::new(cards::Suit::RedJoker, cards::Rank::Rank1),
+            &rules
+        );
+        trick.play_card(
+            pos::PlayerPos::P1,
+            cards::Card::new(cards::Suit::BlackJoker, cards::Rank::Rank1),
+            &rules
+        );
+        assert_eq!( trick.winner, pos::PlayerPos::P1);
+    }
+
+    #[test]
+    fn test_suit() {
+        let rules = RuleSet::default();
+        let mut trick = Trick::new(pos::PlayerPos::P2, 3);
+        assert_eq!(trick.suit(), None);
+
+        // A led joker does not set the suit
         trick.play_card(
             pos::PlayerPos::P2,
-            cards::Card::new(cards::Suit::Heart, cards::Rank::Rank10)
+            cards::Card::new(cards::Suit::RedJoker, cards::Rank::Rank1),
+            &rules
+        );
+        assert_eq!(trick.suit(), None);
+
+        trick.play_card(
+            pos::PlayerPos::P0,
+            cards::Card::new(cards::Suit::Diamond, cards::Rank::Rank4),
+            &rules
+        );
+        assert_eq!(trick.suit(), Some(cards::Suit::Diamond));
+
+        // The joker still wins, a stronger diamond does not matter
+        trick.play_card(
+            pos::PlayerPos::P1,
+            cards::Card::new(cards::Suit::Diamond, cards::Rank::Rank9),
+            &rules
+        );
+        assert_eq!(trick.suit(), Some(cards::Suit::Diamond));
+        assert_eq!(trick.winner, pos::PlayerPos::P2);
+    }
+
+    #[test]
+    fn test_face_strength() {
+        let rules = RuleSet { face_strength: FaceStrength::High, ..RuleSet::default() };
+        let mut trick = Trick::default();
+        trick.play_card(
+            pos::PlayerPos::P0,
+            cards::Card::new(cards::Suit::Club, cards::Rank::Rank10),
+            &rules
+        );
+        trick.play_card(
+            pos::PlayerPos::P1,
+            cards::Card::new(cards::Suit::Club, cards::Rank::RankJ),
+            &rules
         );
         assert_eq!( trick.winner, pos::PlayerPos::P1);
     }
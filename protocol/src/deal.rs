@@ -1,29 +1,30 @@
 use serde::{Deserialize, Serialize};
 
-use thevalley_game::{NB_PLAYERS, cards, pos, deal, trick, deal_hands};
+use thevalley_game::{cards, pos, deal, trick, rules, deal_seeded_hands, Seed};
 
 /// Describe a single deal.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum Deal {
     /// The deal is in the main playing phase
     Playing(deal::DealState),
 }
 
 impl Deal {
-    // Creates a new deal, starting with an auction.
-    pub fn new(_first: pos::PlayerPos) -> Self {
-        let (hands, _river) = deal_hands();
-        Deal::Playing(deal::DealState::new(pos::PlayerPos::P0, hands))
+    // Creates a new deal for `nb_players` players, with hands determined by `seed`.
+    pub fn new(first: pos::PlayerPos, nb_players: usize, rules: &rules::RuleSet, seed: Seed) -> Self {
+        let (hands, river) = deal_seeded_hands(seed, nb_players, rules);
+        Deal::Playing(deal::DealState::new(first, hands, river, rules.clone()))
     }
 
     pub fn next_player(&self) -> pos::PlayerPos {
         match self {
-            &Deal::Playing(ref deal) => deal.next_player(),
+            Deal::Playing(deal) => deal.next_player(),
         }
     }
 
-    pub fn hands(&self) -> [cards::Hand; NB_PLAYERS] {
+    pub fn hands(&self) -> &[cards::Hand] {
         match self {
-            &Deal::Playing(ref deal) => deal.hands(),
+            Deal::Playing(deal) => deal.hands(),
         }
     }
 
@@ -42,9 +43,15 @@ impl Deal {
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct DealSnapshot {
-    pub hand: cards::Hand,
+    /// Hand of the player, `None` for a spectator
+    pub hand: Option<cards::Hand>,
+    /// Hands of every seat, only sent to the spectators of an open table
+    #[serde(default)]
+    pub hands: Option<Vec<cards::Hand>>,
     pub current: pos::PlayerPos,
-    pub scores: [f32; NB_PLAYERS],
+    pub scores: Vec<f32>,
     pub last_trick: trick::Trick,
+    /// Number of cards left to draw
+    pub stock_size: usize,
     // pub tricks: Vec<trick::Trick>,
 }
@@ -1,70 +1,132 @@
 use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use thevalley_game::{NB_PLAYERS, cards, pos, deal, trick};
-use webgame_protocol::{GameState, PlayerInfo, ProtocolErrorKind};
-use crate::{ ProtocolError };
+use thevalley_game::{bot, cards, pos, deal, trick, deal_seed, random_seed, Seed, STATE_VERSION};
+use thevalley_game::record::{DealRecord, GameRecord, PlayRecord};
+use webgame_protocol::{GameState, PlayerInfo};
+use crate::{ ErrorCode, ProtocolError };
 
 use crate::turn::Turn;
 use crate::deal::{Deal, DealSnapshot};
 use crate::player::{PlayerRole, GamePlayerState};
+use crate::options::{GameOptions, MatchEnd, TimeControl, TimeoutAction};
 
+/// State of a table, serializable to save running games.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ValleyGameState {
+    #[serde(deserialize_with = "thevalley_game::deserialize_version")]
+    version: u32,
     players: BTreeMap<Uuid, GamePlayerState>,
     turn: Turn,
     deal: Deal,
     first: pos::PlayerPos,
-    scores: Vec<[f32; NB_PLAYERS]>,
+    scores: Vec<Vec<f32>>,
+    options: GameOptions,
+    seed: Seed,
+    history: Vec<DealRecord>,
+    /// Name of the strategy of each computer player
+    #[serde(default)]
+    bots: BTreeMap<Uuid, String>,
+    /// Number of the last event of the game
+    #[serde(default)]
+    seq: u64,
+    /// Events not sent to the players yet
+    #[serde(skip)]
+    events: Vec<GameEvent>,
+    /// Milliseconds left to each seat, when the time is limited
+    #[serde(default)]
+    clocks: Vec<u64>,
+    /// Seat whose clock is running, and since when
+    #[serde(default)]
+    clock_start: Option<(pos::PlayerPos, u64)>,
+    /// Turn the seated players have to get ready for, and since when
+    #[serde(default)]
+    ready_start: Option<(Turn, u64)>,
+    /// A new deal was dealt since the events were last taken
+    #[serde(skip)]
+    dealt: bool,
+    /// Tasks of the server running for this game
+    #[serde(skip)]
+    drivers: Vec<Driver>,
+}
+
+/// Task of the server working on a game in the background.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Driver {
+    /// Plays for the computer players
+    Bots,
+    /// Handles the timeouts of the players
+    Clocks,
+}
+
+/// Milliseconds the seated players have to get ready for the next trick or deal, in a timed game
+pub const READY_TIME: u64 = 30_000;
+
+// Milliseconds since the epoch, for the clocks of the players
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
 }
 
 impl Default for ValleyGameState {
     fn default() -> ValleyGameState {
+        let options = GameOptions::default();
+        let seed = random_seed();
         ValleyGameState {
+            version: STATE_VERSION,
             players: BTreeMap::new(),
             turn: Turn::Pregame,
-            deal: Deal::new(pos::PlayerPos::P0),
+            deal: Deal::new(pos::PlayerPos::P0, options.nb_players, &options.rules, deal_seed(&seed, 0)),
             first: pos::PlayerPos::P0,
             scores: vec![],
+            options,
+            seed,
+            history: vec![DealRecord::new(pos::PlayerPos::P0)],
+            bots: BTreeMap::new(),
+            seq: 0,
+            events: vec![],
+            clocks: vec![],
+            clock_start: None,
+            ready_start: None,
+            dealt: false,
+            drivers: vec![],
         }
     }
 }
 
 impl GameState< GamePlayerState, GameStateSnapshot> for ValleyGameState {
-    type PlayerPos = pos::PlayerPos;
+    /// Seat of a player, `None` for a spectator
+    type PlayerPos = Option<pos::PlayerPos>;
     type PlayerRole = PlayerRole;
 
+    // Once the seats are taken, newcomers watch the game
     fn is_joinable(&self) -> bool {
-        self.turn == Turn::Pregame
+        true
     }
     
     fn get_players(&self) -> &BTreeMap<Uuid, GamePlayerState> {
         &self.players
     }
 
-    fn add_player(&mut self, player_info: PlayerInfo) -> pos::PlayerPos {
+    fn add_player(&mut self, player_info: PlayerInfo) -> Option<pos::PlayerPos> {
         if self.players.contains_key(&player_info.id) {
             return self.players.get(&player_info.id).unwrap().pos;
         }
 
-        //Default pos
-        let nb_players = self.players.len();
-        let mut newpos = pos::PlayerPos::from_n(nb_players);
-
-        //TODO rendre générique
-        for p in &[ pos::PlayerPos::P0,
-        pos::PlayerPos::P1,
-        ] {
-            if !self.position_taken(*p){
-                newpos = p.clone();
-                break;
-            }
-        }
+        // First free seat, there is none once the game has started
+        let newpos = if self.turn == Turn::Pregame {
+            pos::POSITIONS_LIST.iter()
+                .take(self.options.nb_players)
+                .find(|p| !self.position_taken(**p))
+                .copied()
+        } else {
+            None
+        };
 
         let state = GamePlayerState {
             player: player_info,
-            // pos: pos::PlayerPos::from_n(nb_players),
             pos: newpos,
             role: PlayerRole::Spectator,
             ready: false,
@@ -74,17 +136,21 @@ impl GameState< GamePlayerState, GameStateSnapshot> for ValleyGameState {
     }
 
     fn remove_player(&mut self, player_id: Uuid) -> bool {
+        self.bots.remove(&player_id);
         self.players.remove(&player_id).is_some()
     }
 
     fn set_player_role(&mut self, player_id: Uuid, role: PlayerRole) {
         if let Some(player_state) = self.players.get_mut(&player_id) {
-            player_state.role = role;
+            // Spectators have no seat to play from
+            if player_state.is_seated() {
+                player_state.role = role;
+            }
         }
     }
 
-    fn player_by_pos(&self, position: pos::PlayerPos) -> Option<&GamePlayerState> {
-        self.players.iter().find(|(_uuid, player)| player.pos == position).map(|p| p.1)
+    fn player_by_pos(&self, position: Option<pos::PlayerPos>) -> Option<&GamePlayerState> {
+        position.and_then(|position| self.player_at(position))
     }
 
     // Creates a view of the game for a player
@@ -93,9 +159,14 @@ impl GameState< GamePlayerState, GameStateSnapshot> for ValleyGameState {
         for (&_other_player_id, player_state) in self.players.iter() {
             players.push(player_state.clone());
         }
-        players.sort_by(|a, b| a.pos.to_n().cmp(&b.pos.to_n()));
-        let pos = self.players[&player_id].pos;
-        let scores = [0.0; NB_PLAYERS];
+        // Spectators come last
+        players.sort_by_key(|player| player.pos.map_or(usize::MAX, |pos| pos.to_n()));
+        let pos = self.players.get(&player_id).and_then(|player| player.pos);
+        let hands = if pos.is_none() && self.options.open_table {
+            Some(self.deal.hands().to_vec())
+        } else {
+            None
+        };
         let deal = match self.deal.deal_state() {
             Some(state) => { // In Playing phase
                 let last_trick = if self.turn == Turn::Intertrick && !self.was_last_trick() {
@@ -106,31 +177,63 @@ impl GameState< GamePlayerState, GameStateSnapshot> for ValleyGameState {
                 };
                 // log::debug!("trick {:?}", last_trick.cards);
                 DealSnapshot {
-                    hand: state.hands()[pos as usize],
+                    hand: pos.map(|pos| state.hands()[pos as usize]),
+                    hands,
                     current: state.next_player(),
-                    scores,
+                    scores: state.points(),
                     last_trick,
+                    stock_size: state.stock_size(),
                 }
             },
             None => DealSnapshot { // In bidding phase
-                hand: self.deal.hands()[pos as usize],
+                hand: pos.map(|pos| self.deal.hands()[pos as usize]),
+                hands,
                 current: self.deal.next_player(),
-                scores: [0.0;NB_PLAYERS],
+                scores: vec![0.0; self.options.nb_players],
                 last_trick: trick::Trick::default(),
+                stock_size: 0,
             }
         };
         GameStateSnapshot {
             players,
             scores: self.scores.clone(),
             turn: self.turn,
-            deal
+            deal,
+            options: self.options.clone(),
+            winner: self.winner(),
+            deal_seeds: (0..self.scores.len()).map(|deal| deal_seed(&self.seed, deal)).collect(),
+            seed: if self.turn == Turn::Endgame { Some(self.seed) } else { None },
+            record: if self.turn == Turn::Endgame { Some(self.record()) } else { None },
+            seq: self.seq,
+            clocks: self.clocks_at(now_ms()),
         }
     }
 
     fn set_player_ready(&mut self, player_id: Uuid){
-        let turn = self.turn.clone();
+        let first_event = self.events.len();
+        self.mark_ready(player_id, now_ms());
+        // The server sends a snapshot to everyone next, which includes these events
+        self.events.truncate(first_event);
+        self.dealt = false;
+    }
+
+    fn set_player_not_ready(&mut self, player_id: Uuid) {
+        if let Some(player_state) = self.players.get_mut(&player_id) {
+            player_state.ready = false;
+        }
+    }
+
+}
+
+impl ValleyGameState {
+    fn mark_ready(&mut self, player_id: Uuid, now: u64) {
+        let turn = self.turn;
         if let Some(player_state) = self.players.get_mut(&player_id) {
             player_state.ready = true;
+            if !player_state.is_seated() {
+                // Spectators don't hold up the game
+                return;
+            }
             if turn == Turn::Intertrick {
                 self.update_turn();
             } else {
@@ -140,80 +243,580 @@ impl GameState< GamePlayerState, GameStateSnapshot> for ValleyGameState {
                 let mut count = 0;
                 for player in self.players.values() {
                     if player.role == PlayerRole::PreDeal {
-                        count = count + 1;
+                        count += 1;
                     }
                 }
-                if count == NB_PLAYERS {
+                if count == self.options.nb_players {
                     if self.turn == Turn::Interdeal { // ongoing game
                         self.update_turn();
-                    } else { // new game
-                        // self.turn = Turn::Bidding((bid::AuctionState::Bidding, pos::PlayerPos::P0));
+                    } else if self.turn == Turn::Pregame { // new game
+                        self.turn = Turn::from_deal(&self.deal);
                     }
                 }
 
             }
+            self.ready_bots(now);
+            self.sync_clock(now);
         }
     }
 
-    fn set_player_not_ready(&mut self, player_id: Uuid) {
-        if let Some(player_state) = self.players.get_mut(&player_id) {
-            player_state.ready = false;
-        }
-    }
-
-}
-
-impl ValleyGameState {
     pub fn get_turn(&self) -> Turn {
         self.turn
     }
 
+    /// Returns the player seated at `position`.
+    pub fn player_at(&self, position: pos::PlayerPos) -> Option<&GamePlayerState> {
+        self.players.values().find(|player| player.pos == Some(position))
+    }
+
     fn position_taken(&self, position: pos::PlayerPos) -> bool {
-        self.player_by_pos(position) != None
+        self.player_at(position).is_some()
     }
 
     pub fn players_ready(&self) -> bool {
-        !(self.players.iter().find(|(_, player)| player.ready == false) != None)
+        self.players.values().filter(|player| player.is_seated()).all(|player| player.ready)
+    }
+
+    pub fn get_options(&self) -> &GameOptions {
+        &self.options
+    }
+
+    /// Checks that the players may still choose their role, only before the game starts.
+    ///
+    /// Games stay joinable for spectators once started.
+    pub fn check_role_change(&self) -> Result<(), ProtocolError> {
+        if self.turn != Turn::Pregame {
+            return Err(ProtocolError::from(ErrorCode::GameStarted));
+        }
+        Ok(())
+    }
+
+    /// Changes the game options, only possible before the game starts.
+    pub fn set_options(&mut self, options: GameOptions) -> Result<(), ProtocolError> {
+        if self.turn != Turn::Pregame {
+            return Err(ProtocolError::from(ErrorCode::GameStarted));
+        }
+        if let Err(e) = options.rules.validate(options.nb_players) {
+            return Err(ProtocolError::with_params(ErrorCode::InvalidRules, vec![e.to_string()]));
+        }
+        if self.players.values().any(|p| p.pos.map_or(false, |pos| pos.to_n() >= options.nb_players)) {
+            return Err(ProtocolError::from(ErrorCode::TooManyPlayers));
+        }
+        self.clocks = vec![options.time_control.initial_time().unwrap_or(0); options.nb_players];
+        let seed = options.seed.unwrap_or(self.seed);
+        if options.nb_players != self.options.nb_players || options.rules != self.options.rules || seed != self.seed {
+            self.seed = seed;
+            self.deal = Deal::new(self.first, options.nb_players, &options.rules, deal_seed(&seed, 0));
+            self.history = vec![DealRecord::new(self.first)];
+        }
+        self.options = options;
+        Ok(())
+    }
+
+    /// Returns the seed the deals of this game are made from.
+    pub fn get_seed(&self) -> Seed {
+        self.seed
+    }
+
+    /// Returns the record of the game, with the plays made so far.
+    pub fn record(&self) -> GameRecord {
+        let seats = pos::POSITIONS_LIST.iter()
+            .take(self.options.nb_players)
+            .map(|p| self.player_at(*p).map(|player| player.player.nickname.clone()).unwrap_or_default())
+            .collect();
+        let mut record = GameRecord::new(self.options.rules.clone(), self.seed, seats);
+        record.deals = self.history.clone();
+        record
+    }
+
+    /// Seats a computer player using the strategy called `strategy`, only
+    /// possible before the game starts.
+    ///
+    /// The strategies are implemented by the server, which checks the name.
+    pub fn add_bot(&mut self, strategy: &str) -> Result<Uuid, ProtocolError> {
+        if self.turn != Turn::Pregame {
+            return Err(ProtocolError::from(ErrorCode::GameStarted));
+        }
+        let nb_players = self.options.nb_players;
+        if pos::POSITIONS_LIST.iter().take(nb_players).all(|p| self.position_taken(*p)) {
+            return Err(ProtocolError::from(ErrorCode::NoFreeSeat));
+        }
+        // Random id, without the uuid `v4` feature which the web client can't build
+        let bytes = random_seed();
+        let id = uuid::Builder::from_slice(&bytes[..16])
+            .expect("16 bytes")
+            .set_variant(uuid::Variant::RFC4122)
+            .set_version(uuid::Version::Random)
+            .build();
+        let nickname = format!("bot {} ({})", self.bots.len() + 1, strategy);
+        self.bots.insert(id, strategy.to_string());
+        self.add_player(PlayerInfo { id, nickname });
+        self.mark_ready(id, now_ms());
+        Ok(id)
+    }
+
+    pub fn has_bots(&self) -> bool {
+        !self.bots.is_empty()
+    }
+
+    fn needs_driver(&self, driver: Driver) -> bool {
+        match driver {
+            Driver::Bots => self.has_bots(),
+            Driver::Clocks => self.options.time_control != TimeControl::Unlimited,
+        }
+    }
+
+    /// Returns `true` if the game needs `driver` and it is not running yet:
+    /// the caller must then start it.
+    pub fn claim_driver(&mut self, driver: Driver) -> bool {
+        if !self.needs_driver(driver) || self.drivers.contains(&driver) {
+            return false;
+        }
+        self.drivers.push(driver);
+        true
+    }
+
+    /// Returns `true` if `driver` must stop because the game doesn't need it anymore.
+    pub fn release_driver(&mut self, driver: Driver) -> bool {
+        if self.needs_driver(driver) {
+            return false;
+        }
+        self.drivers.retain(|running| *running != driver);
+        true
+    }
+
+    pub fn is_bot(&self, player_id: Uuid) -> bool {
+        self.bots.contains_key(&player_id)
+    }
+
+    /// Returns the computer player expected to play, if any.
+    pub fn bot_to_play(&self) -> Option<Uuid> {
+        match self.turn {
+            Turn::Playing(pos) => self.player_at(pos)
+                .map(|player| player.player.id)
+                .filter(|id| self.is_bot(*id)),
+            _ => None,
+        }
+    }
+
+    /// Returns the computer player expected to play, with its strategy and
+    /// what it sees of the deal.
+    pub fn bot_observation(&self) -> Option<(Uuid, String, bot::Observation)> {
+        let bot_id = self.bot_to_play()?;
+        let pos = self.players[&bot_id].pos?;
+        let observation = bot::Observation::new(self.deal.deal_state()?, pos);
+        Some((bot_id, self.bots[&bot_id].clone(), observation))
+    }
+
+    /// Makes the computer player whose turn it is play the card chosen by `strategy`.
+    ///
+    /// Returns the bot and the card played.
+    pub fn play_bot(&mut self, strategy: &mut dyn bot::Strategy) -> Option<(Uuid, cards::Card)> {
+        let (bot_id, _, observation) = self.bot_observation()?;
+        let card = strategy.play(&observation);
+        self.set_play(bot_id, card).ok()?;
+        Some((bot_id, card))
+    }
+
+    // Computer players are always ready to go on.
+    fn ready_bots(&mut self, now: u64) {
+        let waiting: Vec<Uuid> = self.bots.keys()
+            .filter(|id| self.players.get(id).map_or(false, |player| !player.ready))
+            .copied()
+            .collect();
+        for id in waiting {
+            self.mark_ready(id, now);
+        }
+    }
+
+    /// Returns the sum of the scores of all deals played.
+    pub fn total_scores(&self) -> Vec<f32> {
+        let mut totals = vec![0.0; self.options.nb_players];
+        for score in self.scores.iter() {
+            for (total, points) in totals.iter_mut().zip(score.iter()) {
+                *total += points;
+            }
+        }
+        totals
+    }
+
+    /// Returns the player with the highest total, if there is no tie.
+    fn leader(&self) -> Option<pos::PlayerPos> {
+        let totals = self.total_scores();
+        let mut leader = None;
+        let mut best = f32::MIN;
+        for (idx, total) in totals.iter().enumerate() {
+            if *total > best {
+                best = *total;
+                leader = Some(pos::PlayerPos::from_n(idx));
+            } else if *total == best {
+                leader = None;
+            }
+        }
+        leader
+    }
+
+    fn is_match_over(&self) -> bool {
+        match self.options.match_end {
+            MatchEnd::Deals(count) => self.scores.len() >= count,
+            MatchEnd::TargetScore(target) => {
+                self.total_scores().iter().any(|total| *total >= target) && self.leader().is_some()
+            }
+        }
+    }
+
+    /// Returns the winner of the match once it is over (`None` on a draw).
+    pub fn winner(&self) -> Option<pos::PlayerPos> {
+        if self.turn == Turn::Endgame {
+            self.leader()
+        } else {
+            None
+        }
     }
 
     pub fn update_turn(&mut self){
+        let previous = self.turn;
         self.turn = if !self.players_ready() {
             Turn::Intertrick
         } else if self.was_last_trick() {
             self.end_deal();
-            Turn::Interdeal
+            if self.is_match_over() {
+                Turn::Endgame
+            } else {
+                Turn::Interdeal
+            }
         } else {
             if self.turn == Turn::Interdeal {
                 self.next_deal();
             }
             Turn::from_deal(&self.deal)
+        };
+        if self.turn == Turn::Endgame && previous != Turn::Endgame {
+            self.push_event(PlayEvent::MatchEnded { winner: self.winner() });
         }
     }
 
     fn was_last_trick(&self) -> bool {
-        let p0 = self.player_by_pos(pos::PlayerPos::P0).unwrap();
+        let p0 = self.player_at(pos::PlayerPos::P0).unwrap();
         self.turn == Turn::Intertrick && p0.role == PlayerRole::Unknown
     }
 
     pub fn set_play(&mut self, pid: Uuid, card: cards::Card) -> Result<(), ProtocolError> {
-        let pos = self.players.get(&pid).map(|p| p.pos).unwrap();
+        self.set_play_at(pid, card, now_ms())
+    }
+
+    /// Plays `card` for `pid`, `now` being the time in milliseconds since the epoch.
+    pub fn set_play_at(&mut self, pid: Uuid, card: cards::Card, now: u64) -> Result<(), ProtocolError> {
+        let pos = self.players.get(&pid).and_then(|p| p.pos).ok_or(
+            ProtocolError::from(ErrorCode::SpectatorCantPlay)
+        )?;
         let state = self.deal.deal_state_mut().ok_or(
-            ProtocolError::new(ProtocolErrorKind::InternalError, "Unknown deal state")
+            ProtocolError::from(ErrorCode::UnknownDealState)
         )?;
-        match state.play_card(pos, card)? {
-            deal::TrickResult::Nothing => (),
-            deal::TrickResult::TrickOver(_winner, deal::DealResult::Nothing) => self.end_trick(),
-            deal::TrickResult::TrickOver(_winner, deal::DealResult::GameOver{points: _}) => {
-                self.end_last_trick();
+        let before = state.hands().to_vec();
+        let mut trick_cards = state.current_trick().cards.clone();
+        trick_cards[pos.to_n()] = Some(card);
+        let result = state.play_card(pos, card)?;
+        // Cards drawn from the stock at the end of the trick
+        let drawn: Vec<(pos::PlayerPos, cards::Card)> = state.hands().iter().zip(before.iter())
+            .enumerate()
+            .filter_map(|(seat, (after, before))| {
+                let new = *after - *before;
+                if new.is_empty() { None } else { Some((pos::PlayerPos::from_n(seat), new.get_card())) }
+            })
+            .collect();
+        let (deal_points, stock_size) = (state.points(), state.stock_size());
+        self.stop_clock(pos, now);
+        if let Some(deal_record) = self.history.last_mut() {
+            deal_record.plays.push(PlayRecord { pos, card });
+        }
+        let first_event = self.events.len();
+        self.push_event(PlayEvent::Play(pid, card));
+        if let deal::TrickResult::TrickOver(winner, deal_result) = result {
+            self.push_event(PlayEvent::TrickWon { winner, cards: trick_cards, points: deal_points, stock_size });
+            for (player, card) in drawn {
+                self.push_event(PlayEvent::CardDrawn { player, card: Some(card) });
+            }
+            match deal_result {
+                deal::DealResult::Nothing => self.end_trick(),
+                deal::DealResult::GameOver{points} => {
+                    self.push_event(PlayEvent::DealEnded {
+                        points: points.clone(),
+                        seed: deal_seed(&self.seed, self.scores.len()),
+                    });
+                    self.scores.push(points);
+                    self.end_last_trick();
+                }
             }
         }
         self.update_turn();
+        self.ready_bots(now);
+        self.restart_clock(now);
+        self.settle_events(first_event, now);
         Ok(())
     }
 
+    // Events are applied at once by the players, they all lead to the new turn
+    fn settle_events(&mut self, first_event: usize, now: u64) {
+        let turn = self.turn;
+        let clocks = self.clocks_at(now);
+        for event in self.events[first_event..].iter_mut() {
+            event.turn = turn;
+            event.clocks = clocks.clone();
+        }
+    }
+
+    /// Returns the milliseconds left to the player at `pos`, `None` when the time is unlimited.
+    pub fn time_left(&self, pos: pos::PlayerPos, now: u64) -> Option<u64> {
+        self.options.time_control.initial_time()?;
+        let left = self.clocks.get(pos.to_n()).copied().unwrap_or(0);
+        Some(match self.clock_start {
+            Some((running, start)) if running == pos => left.saturating_sub(now.saturating_sub(start)),
+            _ => match self.options.time_control {
+                // The next move gets the full time
+                TimeControl::PerMove(seconds) => seconds * 1000,
+                _ => left,
+            },
+        })
+    }
+
+    fn clocks_at(&self, now: u64) -> Option<Vec<u64>> {
+        self.options.time_control.initial_time()?;
+        Some(pos::POSITIONS_LIST.iter()
+            .take(self.options.nb_players)
+            .map(|pos| self.time_left(*pos, now).unwrap_or(0))
+            .collect())
+    }
+
+    fn set_clock(&mut self, pos: pos::PlayerPos, time: u64) {
+        if self.clocks.len() <= pos.to_n() {
+            self.clocks.resize(pos.to_n() + 1, 0);
+        }
+        self.clocks[pos.to_n()] = time;
+    }
+
+    // Starts the clock of the player whose turn it is, or the ready clock
+    // between tricks and deals, if not running yet
+    fn sync_clock(&mut self, now: u64) {
+        if self.options.time_control == TimeControl::Unlimited {
+            self.clock_start = None;
+            self.ready_start = None;
+            return;
+        }
+        match self.turn {
+            Turn::Playing(pos) => {
+                self.ready_start = None;
+                if self.clock_start.map(|(running, _)| running) != Some(pos) {
+                    if let TimeControl::PerMove(seconds) = self.options.time_control {
+                        self.set_clock(pos, seconds * 1000);
+                    }
+                    self.clock_start = Some((pos, now));
+                }
+            }
+            Turn::Intertrick | Turn::Interdeal => {
+                self.clock_start = None;
+                if self.ready_start.map(|(turn, _)| turn) != Some(self.turn) {
+                    self.ready_start = Some((self.turn, now));
+                }
+            }
+            _ => {
+                self.clock_start = None;
+                self.ready_start = None;
+            }
+        }
+    }
+
+    // Starts the clock of the next player, even if it's the one who just played
+    fn restart_clock(&mut self, now: u64) {
+        self.clock_start = None;
+        self.ready_start = None;
+        self.sync_clock(now);
+    }
+
+    // Stops the clock of the player at `pos`, who just played
+    fn stop_clock(&mut self, pos: pos::PlayerPos, now: u64) {
+        if let Some(left) = self.time_left(pos, now) {
+            let increment = match self.options.time_control {
+                TimeControl::Bank { increment, .. } => increment * 1000,
+                _ => 0,
+            };
+            self.set_clock(pos, left + increment);
+        }
+        self.clock_start = None;
+    }
+
+    /// Returns `true` if `player_id` has a seat at the table.
+    pub fn is_seated(&self, player_id: Uuid) -> bool {
+        self.players.get(&player_id).map_or(false, |player| player.is_seated())
+    }
+
+    /// Takes over the table `saved`, kept before a restart of the server.
+    ///
+    /// Only possible before the game starts. The players of `saved` keep their
+    /// seats, even those who haven't come back yet; the others watch.
+    pub fn restore(&mut self, saved: ValleyGameState) -> Result<(), ProtocolError> {
+        if self.turn != Turn::Pregame {
+            return Err(ProtocolError::from(ErrorCode::GameStarted));
+        }
+        let newcomers: Vec<GamePlayerState> = self.players.values()
+            .filter(|player| !saved.players.contains_key(&player.player.id))
+            .map(|player| GamePlayerState {
+                pos: None,
+                role: PlayerRole::Spectator,
+                ready: false,
+                ..player.clone()
+            })
+            .collect();
+        // The tasks of this table keep running
+        let drivers = std::mem::take(&mut self.drivers);
+        *self = saved;
+        self.drivers = drivers;
+        for player in newcomers {
+            self.players.insert(player.player.id, player);
+        }
+        self.resume_clocks();
+        Ok(())
+    }
+
+    /// Restarts the running clocks of a game restored after a server restart.
+    pub fn resume_clocks(&mut self) {
+        self.resume_clocks_at(now_ms())
+    }
+
+    /// Same as `resume_clocks`, `now` being the time in milliseconds since the epoch.
+    ///
+    /// The time elapsed while the server was down doesn't count.
+    pub fn resume_clocks_at(&mut self, now: u64) {
+        if let Some((pos, start)) = self.clock_start {
+            // Time left when the game was saved, at the last change
+            let left = self.time_left(pos, start).unwrap_or(0);
+            self.set_clock(pos, left);
+            self.clock_start = Some((pos, now));
+        }
+        if let Some((turn, _)) = self.ready_start {
+            self.ready_start = Some((turn, now));
+        }
+    }
+
+    /// Handles the timeout of the player whose turn it is, if its time has run out,
+    /// or of the seated players who didn't get ready in `READY_TIME`.
+    ///
+    /// Returns `true` if the game changed.
+    pub fn check_clock(&mut self) -> bool {
+        self.check_clock_at(now_ms())
+    }
+
+    /// Same as `check_clock`, `now` being the time in milliseconds since the epoch.
+    pub fn check_clock_at(&mut self, now: u64) -> bool {
+        if let Some((turn, start)) = self.ready_start {
+            return now.saturating_sub(start) >= READY_TIME && self.ready_late_players(turn, now);
+        }
+        let pos = match self.clock_start {
+            Some((pos, _)) if self.time_left(pos, now) == Some(0) => pos,
+            _ => return false,
+        };
+        let player_id = match self.player_at(pos) {
+            Some(player) => player.player.id,
+            None => return false,
+        };
+        let first_event = self.events.len();
+        self.push_event(PlayEvent::PlayerTimedOut(player_id));
+        match self.options.timeout_action {
+            TimeoutAction::AutoPlay => {
+                use bot::Strategy;
+                let card = match self.deal.deal_state() {
+                    Some(state) => bot::GreedyStrategy.play(&bot::Observation::new(state, pos)),
+                    None => return false,
+                };
+                if self.set_play_at(player_id, card, now).is_err() {
+                    return false;
+                }
+            }
+            TimeoutAction::ForfeitDeal => self.forfeit_deal(pos, now),
+        }
+        self.settle_events(first_event, now);
+        true
+    }
+
+    // Gets ready the seated players holding up the game at `turn`
+    fn ready_late_players(&mut self, turn: Turn, now: u64) -> bool {
+        let late: Vec<Uuid> = self.players.values()
+            .filter(|player| player.is_seated() && !player.ready)
+            .map(|player| player.player.id)
+            .collect();
+        let first_event = self.events.len();
+        for player_id in late {
+            // The last one may start the next turn, which gets its own ready clock
+            if self.turn != turn {
+                break;
+            }
+            self.push_event(PlayEvent::PlayerTimedOut(player_id));
+            self.mark_ready(player_id, now);
+        }
+        self.settle_events(first_event, now);
+        self.events.len() > first_event
+    }
+
+    // Ends the deal at once, the player at `pos` losing its points
+    fn forfeit_deal(&mut self, pos: pos::PlayerPos, now: u64) {
+        let mut points = match self.deal.deal_state() {
+            Some(state) => state.points(),
+            None => return,
+        };
+        points[pos.to_n()] = 0.0;
+        self.stop_clock(pos, now);
+        self.push_event(PlayEvent::DealEnded {
+            points: points.clone(),
+            seed: deal_seed(&self.seed, self.scores.len()),
+        });
+        self.scores.push(points);
+        self.end_last_trick();
+        self.update_turn();
+        self.ready_bots(now);
+        self.restart_clock(now);
+    }
+
+    fn push_event(&mut self, event: PlayEvent) {
+        self.seq += 1;
+        self.events.push(GameEvent { seq: self.seq, turn: self.turn, clocks: None, event });
+    }
+
+    /// Returns the events of the plays since the last call, or `None` when
+    /// the players need a full snapshot: a new deal was dealt or the match is over.
+    pub fn take_events(&mut self) -> Option<Vec<GameEvent>> {
+        let events = std::mem::take(&mut self.events);
+        let dealt = std::mem::take(&mut self.dealt);
+        let match_ended = events.iter().any(|e| matches!(e.event, PlayEvent::MatchEnded { .. }));
+        if dealt || match_ended {
+            None
+        } else {
+            Some(events)
+        }
+    }
+
+    /// Returns `event` as seen by `player_id`, without the cards it can't see.
+    pub fn event_view(&self, player_id: Uuid, event: &GameEvent) -> GameEvent {
+        let mut event = event.clone();
+        if let PlayEvent::CardDrawn { player, card } = &mut event.event {
+            if !self.sees_hand(player_id, player.to_n()) {
+                *card = None;
+            }
+        }
+        event
+    }
+
+    // A player sees its own hand, spectators of an open table see them all
+    fn sees_hand(&self, player_id: Uuid, seat: usize) -> bool {
+        match self.players.get(&player_id).map(|player| player.pos) {
+            Some(Some(pos)) => pos.to_n() == seat,
+            Some(None) => self.options.open_table,
+            None => false,
+        }
+    }
+
     fn end_trick(&mut self) {
         for player in self.players.values_mut() {
-            if player.role != PlayerRole::Spectator {
+            if player.is_seated() {
                 player.ready = false;
             }
         }
@@ -221,7 +824,7 @@ impl ValleyGameState {
 
     fn end_last_trick(&mut self) {
         for player in self.players.values_mut() {
-            if player.role != PlayerRole::Spectator {
+            if player.is_seated() {
                 player.ready = false;
                 player.role = PlayerRole::Unknown;
             }
@@ -231,23 +834,67 @@ impl ValleyGameState {
     fn end_deal(&mut self) {
         self.turn = Turn::Interdeal;
         for player in self.players.values_mut() {
-            if player.role != PlayerRole::Spectator {
+            if player.is_seated() {
                 player.ready = false;
             }
         }
     }
 
     fn next_deal(&mut self) {
-        self.first = self.first.next();
-        // let auction = bid::Auction::new(self.first);
-        // self.deal = Deal::Bidding(auction);
+        self.first = self.first.next(self.options.nb_players);
+        let seed = deal_seed(&self.seed, self.scores.len());
+        self.deal = Deal::new(self.first, self.options.nb_players, &self.options.rules, seed);
+        self.history.push(DealRecord::new(self.first));
+        self.dealt = true;
     }
 
 }
 
+/// Change of the game, sent to the players instead of a full snapshot.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum PlayEvent {
-    Play( Uuid, cards::Card)
+    /// A player played a card
+    Play( Uuid, cards::Card),
+    /// A trick was won
+    TrickWon {
+        winner: pos::PlayerPos,
+        /// Cards of the trick, by seat
+        cards: Vec<Option<cards::Card>>,
+        /// Points of the deal so far
+        points: Vec<f32>,
+        /// Number of cards left in the stock once the players drew
+        stock_size: usize,
+    },
+    /// A player drew a card from the stock
+    CardDrawn {
+        player: pos::PlayerPos,
+        /// Only set when the receiver can see the hand of the player
+        card: Option<cards::Card>,
+    },
+    /// The deal is over, its points are added to the scores
+    DealEnded {
+        points: Vec<f32>,
+        seed: Seed,
+    },
+    /// The match is over, `None` on a draw
+    MatchEnded {
+        winner: Option<pos::PlayerPos>,
+    },
+    /// A player ran out of time
+    PlayerTimedOut(Uuid),
+}
+
+/// Event numbered in the order of the game.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GameEvent {
+    /// Number of the event, snapshots carry the number of the last event they include
+    pub seq: u64,
+    /// Turn once the event is applied
+    pub turn: Turn,
+    /// Milliseconds left to each seat once the event is applied, when the time is limited
+    #[serde(default)]
+    pub clocks: Option<Vec<u64>>,
+    pub event: PlayEvent,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -255,7 +902,20 @@ pub struct GameStateSnapshot {
     pub players: Vec<GamePlayerState>,
     pub turn: Turn,
     pub deal: DealSnapshot,
-    pub scores: Vec<[f32; NB_PLAYERS]>,
+    pub scores: Vec<Vec<f32>>,
+    pub options: GameOptions,
+    /// Winner of the match, set when the turn is `Endgame`
+    pub winner: Option<pos::PlayerPos>,
+    /// Seeds of the deals already played
+    pub deal_seeds: Vec<Seed>,
+    /// Seed of the game, revealed when the turn is `Endgame`
+    pub seed: Option<Seed>,
+    /// Record of the game, set when the turn is `Endgame`
+    pub record: Option<GameRecord>,
+    /// Number of the last event included
+    pub seq: u64,
+    /// Milliseconds left to each seat, when the time is limited
+    pub clocks: Option<Vec<u64>>,
 }
 
 impl webgame_protocol::GameStateSnapshot for GameStateSnapshot {
@@ -271,21 +931,116 @@ impl GameStateSnapshot {
         }
     }
 
+    /// Returns the seated players, in the order of their seats.
+    pub fn seated_players(&self) -> Vec<&GamePlayerState> {
+        self.players.iter().filter(|p| p.is_seated()).collect()
+    }
+
     pub fn pos_player_name(&self, pos: pos::PlayerPos) -> String {
         self.players.iter()
-            .find(|p| p.pos == pos)
+            .find(|p| p.pos == Some(pos))
             .map(|found| &found.player.nickname)
             .unwrap() // panic on invalid pos
             .into()
     }
 
+    /// Applies an event following this snapshot, as seen by `player_id`.
+    ///
+    /// Events already included in the snapshot are ignored. Returns `false`
+    /// if some events were missed: a new snapshot is needed.
+    pub fn apply_event(&mut self, player_id: Uuid, event: &GameEvent) -> bool {
+        if event.seq <= self.seq {
+            return true;
+        }
+        if event.seq != self.seq + 1 {
+            return false;
+        }
+        let my_pos = self.players.iter().find(|p| p.player.id == player_id).and_then(|p| p.pos);
+        let nb_players = self.options.nb_players;
+        match &event.event {
+            PlayEvent::Play(id, card) => {
+                let pos = match self.players.iter().find(|p| p.player.id == *id).and_then(|p| p.pos) {
+                    Some(pos) => pos,
+                    None => return false,
+                };
+                let deal = &mut self.deal;
+                if deal.last_trick.cards.iter().all(Option::is_some) {
+                    deal.last_trick = trick::Trick::new(deal.last_trick.winner, nb_players);
+                }
+                deal.last_trick.play_card(pos, *card, &self.options.rules);
+                if my_pos == Some(pos) {
+                    if let Some(hand) = deal.hand.as_mut() {
+                        hand.remove(*card);
+                    }
+                }
+                if let Some(hands) = deal.hands.as_mut() {
+                    hands[pos.to_n()].remove(*card);
+                }
+            }
+            PlayEvent::TrickWon { winner, cards, points, stock_size } => {
+                let deal = &mut self.deal;
+                deal.last_trick.cards = cards.clone();
+                deal.last_trick.winner = *winner;
+                deal.current = *winner;
+                deal.scores = points.clone();
+                deal.stock_size = *stock_size;
+                if let Turn::Playing(_) = event.turn {
+                    deal.last_trick = trick::Trick::new(*winner, nb_players);
+                }
+                // Computer players are ready again at once, which only shows in the next snapshot
+                for player in self.players.iter_mut().filter(|p| p.is_seated()) {
+                    player.ready = false;
+                }
+            }
+            PlayEvent::CardDrawn { player, card } => {
+                if let Some(card) = card {
+                    let deal = &mut self.deal;
+                    if my_pos == Some(*player) {
+                        if let Some(hand) = deal.hand.as_mut() {
+                            hand.add(*card);
+                        }
+                    }
+                    if let Some(hands) = deal.hands.as_mut() {
+                        hands[player.to_n()].add(*card);
+                    }
+                }
+            }
+            PlayEvent::DealEnded { points, seed } => {
+                self.scores.push(points.clone());
+                self.deal_seeds.push(*seed);
+                for player in self.players.iter_mut().filter(|p| p.is_seated()) {
+                    player.ready = false;
+                    player.role = PlayerRole::Unknown;
+                }
+            }
+            PlayEvent::MatchEnded { winner } => self.winner = *winner,
+            PlayEvent::PlayerTimedOut(id) => {
+                // Between tricks and deals, the late players are made ready
+                if !matches!(self.turn, Turn::Playing(_)) {
+                    if let Some(player) = self.players.iter_mut().find(|p| p.player.id == *id) {
+                        player.ready = true;
+                    }
+                }
+            }
+        }
+        self.seq = event.seq;
+        self.turn = event.turn;
+        if event.clocks.is_some() {
+            self.clocks = event.clocks.clone();
+        }
+        if let Turn::Playing(pos) = self.turn {
+            self.deal.current = pos;
+        }
+        true
+    }
+
     pub fn current_player_name(&self) -> String {
         let found_name = self.get_playing_pos().map(|pos| {
             self.pos_player_name(pos)
         });
 
         if let Some(name) = found_name {
-            format!("{}", name)
+            name
         } else {
             "".into()
         }
@@ -301,11 +1056,492 @@ impl Default for GameStateSnapshot {
             scores: vec![],
             turn: Turn::Pregame,
             deal: DealSnapshot {
-                hand: cards::Hand::new(),
+                hand: None,
+                hands: None,
                 current: pos,
-                scores: [0.0;NB_PLAYERS],
-                last_trick: trick::Trick::new(pos),
+                scores: vec![],
+                last_trick: trick::Trick::default(),
+                stock_size: 0,
+            },
+            options: GameOptions::default(),
+            winner: None,
+            deal_seeds: vec![],
+            seed: None,
+            record: None,
+            seq: 0,
+            clocks: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use thevalley_game::bot::GreedyStrategy;
+
+    fn player(n: u128) -> PlayerInfo {
+        PlayerInfo { id: Uuid::from_u128(n), nickname: format!("player {}", n) }
+    }
+
+    // Table of `nb_players` seats, the humans of `humans` seated
+    fn table(nb_players: usize, humans: u128) -> (ValleyGameState, Vec<Uuid>) {
+        let mut game = ValleyGameState::default();
+        game.set_options(GameOptions { nb_players, ..GameOptions::default() }).unwrap();
+        let ids = (1..=humans).map(|n| {
+            game.add_player(player(n));
+            Uuid::from_u128(n)
+        }).collect();
+        (game, ids)
+    }
+
+    fn start(game: &mut ValleyGameState, ids: &[Uuid]) {
+        for id in ids {
+            game.set_player_ready(*id);
+        }
+    }
+
+    // Started table of two humans, the first clock running since 0
+    fn timed_table(time_control: TimeControl, timeout_action: TimeoutAction) -> (ValleyGameState, Vec<Uuid>) {
+        let (mut game, ids) = table(2, 2);
+        let options = GameOptions { time_control, timeout_action, ..game.get_options().clone() };
+        game.set_options(options).unwrap();
+        start(&mut game, &ids);
+        game.restart_clock(0);
+        game.take_events();
+        (game, ids)
+    }
+
+    fn playing(game: &ValleyGameState) -> pos::PlayerPos {
+        match game.get_turn() {
+            Turn::Playing(pos) => pos,
+            turn => panic!("unexpected turn {:?}", turn),
+        }
+    }
+
+    // Plays a card for the player whose turn it is, at `now`
+    fn play_any(game: &mut ValleyGameState, now: u64) -> Uuid {
+        use bot::Strategy;
+        let pos = playing(game);
+        let card = GreedyStrategy.play(&bot::Observation::new(game.deal.deal_state().unwrap(), pos));
+        let id = game.player_at(pos).unwrap().player.id;
+        game.set_play_at(id, card, now).unwrap();
+        id
+    }
+
+    fn events(game: &mut ValleyGameState) -> Vec<PlayEvent> {
+        game.take_events().unwrap().into_iter().map(|e| e.event).collect()
+    }
+
+    #[test]
+    fn test_add_bot() {
+        let (mut game, ids) = table(3, 1);
+        assert!(!game.has_bots());
+        let bot_id = game.add_bot("greedy").unwrap();
+        assert!(game.is_bot(bot_id));
+        assert!(!game.is_bot(ids[0]));
+        let bot = &game.get_players()[&bot_id];
+        assert_eq!(bot.pos, Some(pos::PlayerPos::P1));
+        assert!(bot.ready);
+        assert_eq!(bot.player.nickname, "bot 1 (greedy)");
+
+        game.add_bot("random").unwrap();
+        assert_eq!(game.add_bot("random").unwrap_err().code(), ErrorCode::NoFreeSeat);
+
+        // The game starts once the human is ready
+        assert_eq!(game.get_turn(), Turn::Pregame);
+        start(&mut game, &ids);
+        assert!(matches!(game.get_turn(), Turn::Playing(_)));
+        assert_eq!(game.add_bot("greedy").unwrap_err().code(), ErrorCode::GameStarted);
+    }
+
+    #[test]
+    fn test_play_bot() {
+        let (mut game, ids) = table(2, 1);
+        let bot_id = game.add_bot("greedy").unwrap();
+        start(&mut game, &ids);
+
+        let mut plays = 0;
+        while game.get_turn() != Turn::Endgame {
+            match game.get_turn() {
+                Turn::Playing(pos) if pos == pos::PlayerPos::P1 => {
+                    let (id, kind, observation) = game.bot_observation().unwrap();
+                    assert_eq!((id, kind), (bot_id, "greedy".to_string()));
+                    assert_eq!(observation.pos, pos);
+                    let (id, card) = game.play_bot(&mut GreedyStrategy).unwrap();
+                    assert_eq!(id, bot_id);
+                    assert!(observation.legal_moves.has(card));
+                }
+                Turn::Playing(_) => {
+                    assert!(game.bot_observation().is_none());
+                    assert!(game.play_bot(&mut GreedyStrategy).is_none());
+                    let hand = game.make_snapshot(ids[0]).deal.hand.unwrap();
+                    assert!(hand.list().into_iter().any(|card| game.set_play(ids[0], card).is_ok()));
+                }
+                // Only the human holds up the game: the bot is ready at once
+                Turn::Intertrick | Turn::Interdeal => {
+                    assert!(game.get_players()[&bot_id].ready);
+                    assert!(!game.get_players()[&ids[0]].ready);
+                    game.set_player_ready(ids[0]);
+                }
+                turn => panic!("unexpected turn {:?}", turn),
+            }
+            plays += 1;
+            assert!(plays < 10_000);
+        }
+        assert!(game.winner().is_some() || game.total_scores()[0] == game.total_scores()[1]);
+    }
+
+    #[test]
+    fn test_bots_play_alone() {
+        // Computer players are ready at once, the game goes on after each trick
+        let (mut game, _) = table(2, 0);
+        game.add_bot("greedy").unwrap();
+        game.add_bot("random").unwrap();
+        assert!(matches!(game.get_turn(), Turn::Playing(_)));
+        while game.get_turn() != Turn::Endgame {
+            assert!(matches!(game.get_turn(), Turn::Playing(_)));
+            assert!(game.play_bot(&mut GreedyStrategy).is_some());
+        }
+    }
+
+    #[test]
+    fn test_drivers() {
+        let (mut game, _) = table(2, 1);
+        assert!(!game.claim_driver(Driver::Bots));
+        let bot_id = game.add_bot("greedy").unwrap();
+        assert!(game.claim_driver(Driver::Bots));
+        assert!(!game.claim_driver(Driver::Bots));
+        assert!(!game.release_driver(Driver::Bots));
+
+        // A restored game has no driver running
+        let json = serde_json::to_string(&game).unwrap();
+        let mut restored: ValleyGameState = serde_json::from_str(&json).unwrap();
+        assert!(restored.claim_driver(Driver::Bots));
+
+        game.remove_player(bot_id);
+        assert!(game.release_driver(Driver::Bots));
+        assert!(!game.claim_driver(Driver::Bots));
+
+        assert!(!game.claim_driver(Driver::Clocks));
+        let options = GameOptions { time_control: TimeControl::PerMove(10), ..game.get_options().clone() };
+        game.set_options(options).unwrap();
+        assert!(game.claim_driver(Driver::Clocks));
+        assert!(!game.release_driver(Driver::Clocks));
+        game.set_options(GameOptions::default()).unwrap();
+        assert!(game.release_driver(Driver::Clocks));
+    }
+
+    #[test]
+    fn test_clock_auto_play() {
+        let (mut game, _) = timed_table(TimeControl::PerMove(10), TimeoutAction::AutoPlay);
+        let pos = playing(&game);
+        let id = game.player_at(pos).unwrap().player.id;
+        assert_eq!(game.time_left(pos, 4_000), Some(6_000));
+        assert!(!game.check_clock_at(9_999));
+
+        assert!(game.check_clock_at(10_000));
+        let events = events(&mut game);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0], PlayEvent::PlayerTimedOut(id));
+        assert!(matches!(events[1], PlayEvent::Play(played, _) if played == id));
+
+        // The next player gets the full time, from the timeout on
+        let next = pos.next(2);
+        assert_eq!(game.get_turn(), Turn::Playing(next));
+        assert_eq!(game.time_left(next, 10_000), Some(10_000));
+        assert_eq!(game.time_left(next, 13_000), Some(7_000));
+        assert_eq!(game.time_left(pos, 13_000), Some(10_000));
+        assert!(!game.check_clock_at(19_999));
+    }
+
+    #[test]
+    fn test_clock_increment() {
+        let time_control = TimeControl::Bank { total: 60, increment: 5 };
+        let (mut game, _) = timed_table(time_control, TimeoutAction::AutoPlay);
+        let pos = playing(&game);
+        play_any(&mut game, 20_000);
+        assert_eq!(game.time_left(pos, 30_000), Some(45_000));
+        assert_eq!(game.time_left(pos.next(2), 30_000), Some(50_000));
+        assert_eq!(game.make_snapshot(game.player_at(pos).unwrap().player.id).clocks.unwrap().len(), 2);
+
+        // The card played on timeout still earns the increment
+        assert!(game.check_clock_at(80_000));
+        assert_eq!(game.time_left(pos.next(2), 80_000), Some(5_000));
+    }
+
+    #[test]
+    fn test_clock_forfeit_deal() {
+        let (mut game, ids) = timed_table(TimeControl::PerMove(10), TimeoutAction::ForfeitDeal);
+        play_any(&mut game, 1_000);
+        game.take_events();
+        let late = playing(&game);
+        let late_id = game.player_at(late).unwrap().player.id;
+
+        assert!(game.check_clock_at(11_000));
+        let events = events(&mut game);
+        assert_eq!(events[0], PlayEvent::PlayerTimedOut(late_id));
+        match &events[1] {
+            PlayEvent::DealEnded { points, .. } => assert_eq!(points[late.to_n()], 0.0),
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert_eq!(game.scores.len(), 1);
+        assert_eq!(game.scores[0][late.to_n()], 0.0);
+        assert_eq!(game.get_turn(), Turn::Intertrick);
+        assert_eq!(game.clock_start, None);
+
+        // Once ready, the players get a new deal, sent in a snapshot
+        start(&mut game, &ids);
+        assert_eq!(game.get_turn(), Turn::Interdeal);
+        let (_, start) = game.ready_start.unwrap();
+        assert!(game.check_clock_at(start + READY_TIME));
+        assert!(matches!(game.get_turn(), Turn::Playing(_)));
+        assert!(game.take_events().is_none());
+    }
+
+    #[test]
+    fn test_ready_timeout() {
+        let (mut game, ids) = timed_table(TimeControl::PerMove(10), TimeoutAction::AutoPlay);
+        play_any(&mut game, 1_000);
+        play_any(&mut game, 2_000);
+        game.take_events();
+        assert_eq!(game.get_turn(), Turn::Intertrick);
+        assert_eq!(game.clock_start, None);
+
+        game.mark_ready(ids[0], 3_000);
+        assert!(!game.check_clock_at(2_000 + READY_TIME - 1));
+        assert!(game.check_clock_at(2_000 + READY_TIME));
+        assert_eq!(events(&mut game), vec![PlayEvent::PlayerTimedOut(ids[1])]);
+        let pos = playing(&game);
+        assert_eq!(game.clock_start, Some((pos, 2_000 + READY_TIME)));
+        assert_eq!(game.ready_start, None);
+
+        // Untimed games wait for the players
+        let (mut game, ids) = table(2, 2);
+        start(&mut game, &ids);
+        play_any(&mut game, 1_000);
+        play_any(&mut game, 2_000);
+        assert_eq!(game.get_turn(), Turn::Intertrick);
+        assert_eq!(game.ready_start, None);
+        assert!(!game.check_clock_at(u64::MAX));
+    }
+
+    #[test]
+    fn test_play_events() {
+        let (mut game, ids) = table(2, 2);
+        game.set_options(GameOptions { match_end: MatchEnd::Deals(1), ..game.get_options().clone() }).unwrap();
+        start(&mut game, &ids);
+        game.take_events();
+        loop {
+            let seq = game.seq;
+            let id = play_any(&mut game, 0);
+            let events = game.take_events().unwrap();
+            let kinds: Vec<&PlayEvent> = events.iter().map(|e| &e.event).collect();
+            assert_eq!(kinds[0], &PlayEvent::Play(id, game.history[0].plays.last().unwrap().card));
+            for (n, event) in events.iter().enumerate() {
+                assert_eq!(event.seq, seq + 1 + n as u64);
+                assert_eq!(event.turn, game.get_turn());
+            }
+            if game.get_turn() != Turn::Intertrick {
+                assert_eq!(events.len(), 1);
+                continue;
+            }
+            assert!(matches!(kinds[1], PlayEvent::TrickWon { .. }));
+            let drawn = kinds[2..].iter().take_while(|e| matches!(e, PlayEvent::CardDrawn { .. })).count();
+            assert!(drawn == 0 || drawn == 2);
+            if let Some(PlayEvent::DealEnded { points, .. }) = kinds.last() {
+                assert_eq!(kinds.len(), 3 + drawn);
+                assert_eq!(game.scores, vec![points.clone()]);
+                break;
+            }
+            assert_eq!(kinds.len(), 2 + drawn);
+            start(&mut game, &ids);
+            assert_eq!(game.take_events(), Some(vec![]));
+        }
+
+        // The end of the match is sent in the snapshot following the last ready player
+        start(&mut game, &ids);
+        assert_eq!(game.get_turn(), Turn::Endgame);
+        assert_eq!(game.take_events(), Some(vec![]));
+        assert_eq!(game.make_snapshot(ids[0]).seq, game.seq);
+    }
+
+    #[test]
+    fn test_event_view() {
+        let (mut game, ids) = table(2, 3);
+        start(&mut game, &ids);
+        let (player, opponent, spectator) = (ids[0], ids[1], ids[2]);
+        let pos = game.get_players()[&player].pos.unwrap();
+        let event = GameEvent {
+            seq: 1,
+            turn: Turn::Intertrick,
+            clocks: None,
+            event: PlayEvent::CardDrawn { player: pos, card: Some(cards::Card::new(cards::Suit::Heart, cards::Rank::Rank3)) },
+        };
+        assert_eq!(game.event_view(player, &event), event);
+        let hidden = game.event_view(opponent, &event);
+        assert_eq!(hidden.event, PlayEvent::CardDrawn { player: pos, card: None });
+        assert_eq!(hidden.seq, event.seq);
+        assert_eq!(game.event_view(spectator, &event), hidden);
+        assert_eq!(game.event_view(Uuid::from_u128(99), &event), hidden);
+
+        game.options.open_table = true;
+        assert_eq!(game.event_view(spectator, &event), event);
+        assert_eq!(game.event_view(opponent, &event), hidden);
+    }
+
+    #[test]
+    fn test_apply_event() {
+        let (mut game, ids) = table(2, 3);
+        start(&mut game, &ids);
+        let mut views: Vec<GameStateSnapshot> = ids.iter().map(|id| game.make_snapshot(*id)).collect();
+        for _ in 0..3 {
+            play_any(&mut game, 0);
+            play_any(&mut game, 0);
+            let events = game.take_events().unwrap();
+            for (view, id) in views.iter_mut().zip(ids.iter()) {
+                for event in events.iter() {
+                    assert!(view.apply_event(*id, &game.event_view(*id, event)));
+                }
+                assert_eq!(*view, game.make_snapshot(*id));
+            }
+            // Events already applied are ignored
+            let mut view = views[0].clone();
+            assert!(view.apply_event(ids[0], &events[0]));
+            assert_eq!(view, views[0]);
+
+            start(&mut game, &ids);
+            views = ids.iter().map(|id| game.make_snapshot(*id)).collect();
+        }
+
+        // A missed event calls for a new snapshot
+        play_any(&mut game, 0);
+        let events = game.take_events().unwrap();
+        let mut gap = events[0].clone();
+        gap.seq += 1;
+        let view = views[0].clone();
+        assert!(!views[0].apply_event(ids[0], &gap));
+        assert_eq!(views[0], view);
+        assert!(views[0].apply_event(ids[0], &events[0]));
+    }
+
+    #[test]
+    fn test_take_events_new_deal() {
+        let (mut game, _) = table(2, 0);
+        game.set_options(GameOptions { match_end: MatchEnd::Deals(2), ..game.get_options().clone() }).unwrap();
+        game.add_bot("greedy").unwrap();
+        game.add_bot("greedy").unwrap();
+        game.take_events();
+        let mut snapshots = 0;
+        while game.get_turn() != Turn::Endgame {
+            let deals = game.scores.len();
+            game.play_bot(&mut GreedyStrategy).unwrap();
+            // The bots get ready at once: the deal ended and the next one was dealt
+            if game.scores.len() > deals {
+                assert!(game.take_events().is_none());
+                snapshots += 1;
+            } else {
+                assert!(game.take_events().unwrap().iter().all(|e| !matches!(e.event, PlayEvent::DealEnded { .. })));
             }
         }
+        assert_eq!(snapshots, 2);
+    }
+
+    #[test]
+    fn test_spectators() {
+        let (mut game, ids) = table(2, 3);
+        assert_eq!(game.get_players()[&ids[0]].pos, Some(pos::PlayerPos::P0));
+        assert_eq!(game.get_players()[&ids[1]].pos, Some(pos::PlayerPos::P1));
+        // No seat left
+        let spectator = ids[2];
+        assert_eq!(game.get_players()[&spectator].pos, None);
+        assert_eq!(game.get_players()[&spectator].role, PlayerRole::Spectator);
+        game.set_player_role(spectator, PlayerRole::PreDeal);
+        assert_eq!(game.get_players()[&spectator].role, PlayerRole::Spectator);
+
+        // Newcomers of a started game get no seat, even a free one
+        start(&mut game, &ids);
+        game.remove_player(ids[1]);
+        assert_eq!(game.add_player(player(4)), None);
+        assert_eq!(game.get_players()[&Uuid::from_u128(4)].role, PlayerRole::Spectator);
+        assert!(game.player_at(pos::PlayerPos::P1).is_none());
+    }
+
+    #[test]
+    fn test_spectator_snapshot() {
+        let (mut game, ids) = table(2, 3);
+        start(&mut game, &ids);
+        let snapshot = game.make_snapshot(ids[0]);
+        assert!(snapshot.deal.hand.is_some());
+        assert_eq!(snapshot.deal.hands, None);
+        let snapshot = game.make_snapshot(ids[2]);
+        assert_eq!(snapshot.deal.hand, None);
+        assert_eq!(snapshot.deal.hands, None);
+        // Spectators come last
+        assert_eq!(snapshot.players.last().unwrap().player.id, ids[2]);
+
+        game.options.open_table = true;
+        let snapshot = game.make_snapshot(ids[2]);
+        assert_eq!(snapshot.deal.hand, None);
+        assert_eq!(snapshot.deal.hands, Some(game.deal.hands().to_vec()));
+        // Seated players only see their own hand
+        assert_eq!(game.make_snapshot(ids[1]).deal.hands, None);
+    }
+
+    #[test]
+    fn test_check_role_change() {
+        let (mut game, ids) = table(2, 2);
+        assert!(game.check_role_change().is_ok());
+        start(&mut game, &ids);
+        assert_eq!(game.check_role_change().unwrap_err().code(), ErrorCode::GameStarted);
+    }
+
+    #[test]
+    fn test_resume_clocks() {
+        let (mut game, _) = timed_table(TimeControl::PerMove(10), TimeoutAction::AutoPlay);
+        let pos = playing(&game);
+        let json = serde_json::to_string(&game).unwrap();
+        let mut restored: ValleyGameState = serde_json::from_str(&json).unwrap();
+        restored.resume_clocks_at(3_600_000);
+        assert!(!restored.check_clock_at(3_600_000));
+        assert_eq!(restored.time_left(pos, 3_604_000), Some(6_000));
+        assert!(restored.check_clock_at(3_610_000));
+
+        play_any(&mut game, 1_000);
+        play_any(&mut game, 2_000);
+        assert_eq!(game.get_turn(), Turn::Intertrick);
+        game.resume_clocks_at(3_600_000);
+        assert!(!game.check_clock_at(2_000 + READY_TIME));
+        assert!(game.check_clock_at(3_600_000 + READY_TIME));
+    }
+
+    #[test]
+    fn test_restore() {
+        let (mut saved, ids) = table(3, 2);
+        saved.add_bot("greedy").unwrap();
+        start(&mut saved, &ids);
+        play_any(&mut saved, 0);
+        let json = serde_json::to_string(&saved).unwrap();
+        let saved: ValleyGameState = serde_json::from_str(&json).unwrap();
+
+        // Only the second player came back, with a newcomer
+        let mut game = ValleyGameState::default();
+        game.add_player(player(2));
+        game.add_player(player(3));
+        game.set_options(GameOptions { time_control: TimeControl::PerMove(10), ..GameOptions::default() }).unwrap();
+        assert!(game.claim_driver(Driver::Clocks));
+        game.restore(saved.clone()).unwrap();
+        // The clock task of the table stops, the bots need a driver
+        assert!(game.release_driver(Driver::Clocks));
+        assert!(game.claim_driver(Driver::Bots));
+        assert_eq!(game.get_turn(), saved.get_turn());
+        assert_eq!(game.seq, saved.seq);
+        assert_eq!(game.get_players()[&ids[1]], saved.get_players()[&ids[1]]);
+        assert!(!game.is_seated(Uuid::from_u128(3)));
+        assert_eq!(game.get_players()[&Uuid::from_u128(3)].role, PlayerRole::Spectator);
+        // The first player gets the seat back on return
+        assert!(game.is_seated(ids[0]));
+        assert_eq!(game.add_player(player(1)), saved.get_players()[&ids[0]].pos);
+
+        assert_eq!(game.restore(saved).unwrap_err().code(), ErrorCode::GameStarted);
     }
 }
@@ -1,15 +1,25 @@
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-use crate::message::ProtocolError;
-use webgame_protocol::ProtocolErrorKind;
+use crate::message::{ErrorCode, ProtocolError};
 use thevalley_game::{cards, deal};
+use crate::options::GameOptions;
 
 impl From<deal::PlayError> for ProtocolError {
     fn from(error: deal::PlayError) -> Self {
-        ProtocolError::new(
-            ProtocolErrorKind::BadState,
-            format!("play: {}", error)
-       )
+        ErrorCode::from(error).into()
+    }
+}
+
+impl From<deal::PlayError> for ErrorCode {
+    fn from(error: deal::PlayError) -> Self {
+        match error {
+            deal::PlayError::TurnError => ErrorCode::InvalidTurnOrder,
+            deal::PlayError::CardMissing => ErrorCode::CardMissing,
+            deal::PlayError::IncorrectSuit => ErrorCode::IncorrectSuit,
+            deal::PlayError::JokerFirstTrick => ErrorCode::JokerFirstTrick,
+            deal::PlayError::NoLastTrick => ErrorCode::NoLastTrick,
+        }
     }
 }
 
@@ -17,9 +27,34 @@ impl From<deal::PlayError> for ProtocolError {
 #[serde(tag = "cmd", rename_all = "snake_case")]
 pub enum GamePlayCommand {
     Play(PlayCommand),
+    SetOptions(SetOptionsCommand),
+    AddBot(AddBotCommand),
+    /// Asks for a full snapshot, after missing some events
+    Resync,
+    /// The player is ready for the next deal or trick
+    Ready,
+    /// Resumes a table saved before a restart of the server
+    Restore(RestoreCommand),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PlayCommand {
     pub card: cards::Card,
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetOptionsCommand {
+    pub options: GameOptions,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RestoreCommand {
+    /// Id of the saved table, the last game of the player
+    pub game_id: Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AddBotCommand {
+    /// Name of the strategy of the computer player
+    pub strategy: String,
+}
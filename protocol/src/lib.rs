@@ -1,13 +1,18 @@
+// Its suggestion, `Option::is_some_and`, is too recent for the pinned toolchain
+#![allow(clippy::unnecessary_map_or)]
+
 mod game;
 mod message;
 mod game_messages;
 mod player;
 mod turn;
 mod deal;
+mod options;
 
 pub use crate::game::*;
 pub use crate::message::*;
 pub use crate::game_messages::*;
 pub use crate::player::*;
 pub use crate::turn::*;
+pub use crate::options::*;
 // pub(crate) use webgame_protocol;
@@ -1,48 +1,208 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
 use webgame_protocol::{ProtocolError as GenericProtocolError, ProtocolErrorKind, Message as GenericMessage, Command as GenericCommand};
 
 use crate::player::{PlayerRole, GamePlayerState};
-use crate::game::{GameStateSnapshot, PlayEvent};
+use crate::game::{GameEvent, GameStateSnapshot};
 use crate::game_messages::GamePlayCommand;
 
 
+/// Reason of an error, for the clients to translate it or react to it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Error without a code, from the game server
+    Unknown,
+    NotInGame,
+    GameStarted,
+    /// The options give rules which can't be played, the parameter tells why
+    InvalidRules,
+    TooManyPlayers,
+    NoFreeSeat,
+    SpectatorCantPlay,
+    UnknownDealState,
+    InvalidTurnOrder,
+    CardMissing,
+    IncorrectSuit,
+    JokerFirstTrick,
+    NoLastTrick,
+    /// No saved table with this id has the player seated
+    UnknownSavedGame,
+    /// The server has no computer player with this strategy
+    UnknownStrategy,
+}
+
+impl ErrorCode {
+    pub fn kind(self) -> ProtocolErrorKind {
+        match self {
+            ErrorCode::UnknownDealState => ProtocolErrorKind::InternalError,
+            _ => ProtocolErrorKind::BadState,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ErrorCode::Unknown => write!(f, "unknown error"),
+            ErrorCode::NotInGame => write!(f, "not in a game"),
+            ErrorCode::GameStarted => write!(f, "game already started"),
+            ErrorCode::InvalidRules => write!(f, "rules"),
+            ErrorCode::TooManyPlayers => write!(f, "too many players seated"),
+            ErrorCode::NoFreeSeat => write!(f, "no free seat"),
+            ErrorCode::SpectatorCantPlay => write!(f, "spectators can't play"),
+            ErrorCode::UnknownDealState => write!(f, "Unknown deal state"),
+            ErrorCode::InvalidTurnOrder => write!(f, "play: invalid turn order"),
+            ErrorCode::CardMissing => write!(f, "play: you can only play cards you have"),
+            ErrorCode::IncorrectSuit => write!(f, "play: wrong suit played"),
+            ErrorCode::JokerFirstTrick => write!(f, "play: jokers can't be played on the first trick"),
+            ErrorCode::NoLastTrick => write!(f, "play: no trick has been played yet"),
+            ErrorCode::UnknownSavedGame => write!(f, "no saved game to resume"),
+            ErrorCode::UnknownStrategy => write!(f, "unknown bot strategy"),
+        }
+    }
+}
+
+// The errors of the game server only carry a message: the code and its
+// parameters are lost, send a `GameMessage::Error` to keep them.
 impl From<ProtocolError> for GenericProtocolError {
     fn from(error: ProtocolError) -> Self {
         GenericProtocolError::new(
             error.kind,
-            error.message      
+            error.message
        )
     }
 }
 
+impl From<&GenericProtocolError> for ProtocolError {
+    fn from(error: &GenericProtocolError) -> Self {
+        ProtocolError::new(error.kind(), error.message())
+    }
+}
+
+impl From<ErrorCode> for ProtocolError {
+    fn from(code: ErrorCode) -> Self {
+        ProtocolError::with_params(code, vec![])
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ProtocolError {
     kind: ProtocolErrorKind,
+    #[serde(default = "unknown_code")]
+    code: ErrorCode,
+    /// Values completing the code, such as the reason of invalid rules
+    #[serde(default)]
+    params: Vec<String>,
     message: String,
 }
 
+fn unknown_code() -> ErrorCode {
+    ErrorCode::Unknown
+}
+
 impl ProtocolError {
     pub fn new<S: Into<String>>(kind: ProtocolErrorKind, s: S) -> ProtocolError {
         ProtocolError {
             kind,
+            code: ErrorCode::Unknown,
+            params: vec![],
             message: s.into(),
         }
     }
 
+    /// Creates the error of `code`, the message being its english text followed by `params`.
+    pub fn with_params(code: ErrorCode, params: Vec<String>) -> ProtocolError {
+        let message = if params.is_empty() {
+            code.to_string()
+        } else {
+            format!("{}: {}", code, params.join(", "))
+        };
+        ProtocolError {
+            kind: code.kind(),
+            code,
+            params,
+            message,
+        }
+    }
+
     pub fn kind(&self) -> ProtocolErrorKind {
         self.kind
     }
 
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    pub fn params(&self) -> &[String] {
+        &self.params
+    }
+
     pub fn message(&self) -> &str {
         &self.message
     }
 }
 
+/// Message of the game to a player, sent by the game server as a play event.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum GameMessage {
+    /// Change of the game
+    Event(GameEvent),
+    /// Error of a command of the player, with its code
+    Error(ProtocolError),
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SetPlayerRoleCommand {
     pub role: PlayerRole,
 }
 
-pub type Message = GenericMessage<GamePlayerState, GameStateSnapshot, PlayEvent>;
+pub type Message = GenericMessage<GamePlayerState, GameStateSnapshot, GameMessage>;
 pub type Command = GenericCommand<GamePlayCommand, SetPlayerRoleCommand, GameStateSnapshot>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generic_error() {
+        let error = ProtocolError::with_params(ErrorCode::InvalidRules, vec!["not enough cards".into()]);
+        let generic = GenericProtocolError::from(error.clone());
+        assert!(matches!(generic.kind(), ProtocolErrorKind::BadState));
+        assert_eq!(generic.message(), "rules: not enough cards");
+
+        let back = ProtocolError::from(&generic);
+        assert!(matches!(back.kind(), ProtocolErrorKind::BadState));
+        assert_eq!(back.code(), ErrorCode::Unknown);
+        assert!(back.params().is_empty());
+        assert_eq!(back.message(), error.message());
+
+        let generic = GenericProtocolError::new(ProtocolErrorKind::NotFound, "game not found");
+        let error = ProtocolError::from(&generic);
+        assert!(matches!(error.kind(), ProtocolErrorKind::NotFound));
+        assert_eq!(error.code(), ErrorCode::Unknown);
+        assert_eq!(error.message(), "game not found");
+    }
+
+    #[test]
+    fn test_game_message_error() {
+        let error = ProtocolError::from(ErrorCode::IncorrectSuit);
+        let json = serde_json::to_string(&GameMessage::Error(error)).unwrap();
+        let error = match serde_json::from_str(&json).unwrap() {
+            GameMessage::Error(error) => error,
+            message => panic!("unexpected message {:?}", message),
+        };
+        assert!(matches!(error.kind(), ProtocolErrorKind::BadState));
+        assert_eq!(error.code(), ErrorCode::IncorrectSuit);
+        assert_eq!(error.message(), "play: wrong suit played");
+
+        // Errors of older servers have no code
+        let json = r#"{"Error":{"kind":"BadState","message":"play: wrong suit played"}}"#;
+        match serde_json::from_str(json).unwrap() {
+            GameMessage::Error(error) => assert_eq!(error.code(), ErrorCode::Unknown),
+            message => panic!("unexpected message {:?}", message),
+        }
+    }
+}
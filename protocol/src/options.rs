@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+
+use thevalley_game::{NB_PLAYERS, Seed};
+use thevalley_game::rules::RuleSet;
+
+/// Condition ending a match.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchEnd {
+    /// The match ends after the deal where a player reaches this total.
+    TargetScore(f32),
+    /// The match ends after this number of deals.
+    Deals(usize),
+}
+
+impl Default for MatchEnd {
+    fn default() -> MatchEnd {
+        MatchEnd::TargetScore(50.0)
+    }
+}
+
+/// Time given to the players to play their cards.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeControl {
+    /// Players take all the time they want.
+    Unlimited,
+    /// Seconds for the whole match, plus `increment` seconds after each card played.
+    Bank { total: u64, increment: u64 },
+    /// Seconds to play each card.
+    PerMove(u64),
+}
+
+// `#[default]` on variants is too recent for the pinned toolchain
+#[allow(clippy::derivable_impls)]
+impl Default for TimeControl {
+    fn default() -> TimeControl {
+        TimeControl::Unlimited
+    }
+}
+
+impl TimeControl {
+    /// Returns the milliseconds given to each player at the start, `None` when unlimited.
+    pub fn initial_time(self) -> Option<u64> {
+        match self {
+            TimeControl::Unlimited => None,
+            TimeControl::Bank { total, .. } => Some(total * 1000),
+            TimeControl::PerMove(seconds) => Some(seconds * 1000),
+        }
+    }
+}
+
+/// What happens when a player runs out of time.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeoutAction {
+    /// A card is played for the player.
+    AutoPlay,
+    /// The deal ends at once, the player losing its points.
+    ForfeitDeal,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for TimeoutAction {
+    fn default() -> TimeoutAction {
+        TimeoutAction::AutoPlay
+    }
+}
+
+/// Options chosen before the game starts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GameOptions {
+    pub match_end: MatchEnd,
+    /// Number of seats at the table
+    pub nb_players: usize,
+    /// Variant of the rules used for every deal
+    pub rules: RuleSet,
+    /// Seed of the game, to replay the deals of a previous game.
+    ///
+    /// A random seed is used when not set. The first deal of a game uses
+    /// the game seed itself, so a single deal can be replayed from its seed.
+    #[serde(default)]
+    pub seed: Option<Seed>,
+    /// Spectators see the hands of every player
+    #[serde(default)]
+    pub open_table: bool,
+    #[serde(default)]
+    pub time_control: TimeControl,
+    #[serde(default)]
+    pub timeout_action: TimeoutAction,
+}
+
+impl Default for GameOptions {
+    fn default() -> GameOptions {
+        GameOptions {
+            match_end: MatchEnd::default(),
+            nb_players: NB_PLAYERS,
+            rules: RuleSet::default(),
+            seed: None,
+            open_table: false,
+            time_control: TimeControl::default(),
+            timeout_action: TimeoutAction::default(),
+        }
+    }
+}
@@ -24,7 +24,8 @@ pub enum PlayerAction {
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct GamePlayerState {
     pub player: PlayerInfo,
-    pub pos: pos::PlayerPos,
+    /// Seat of the player, `None` for a spectator
+    pub pos: Option<pos::PlayerPos>,
     pub role: PlayerRole,
     pub ready: bool,
 }
@@ -36,9 +37,13 @@ impl PlayerState for GamePlayerState {
 }
 
 impl GamePlayerState {
+    pub fn is_seated(&self) -> bool {
+        self.pos.is_some()
+    }
+
     pub fn get_turn_player_action(&self, turn: Turn) -> Option<PlayerAction> {
         match turn {
-            Turn::Playing(pos) if pos == self.pos => Some(PlayerAction::Play),
+            Turn::Playing(pos) if Some(pos) == self.pos => Some(PlayerAction::Play),
             _ => None
         }
     }
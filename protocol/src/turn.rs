@@ -15,7 +15,6 @@ pub enum Turn {
 
 impl fmt::Display for Turn {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let strpos;
         write!(
             f,
             "{}",
@@ -23,11 +22,8 @@ impl fmt::Display for Turn {
                 Turn::Pregame => "pre-game",
                 Turn::Intertrick => "inter trick",
                 Turn::Interdeal => "inter deal",
-                Turn::Playing(_pos) => {
-                    strpos = format!("playing");
-                    // strpos = format!("{:?} to play", pos);
-                    &strpos
-                }
+                // Turn::Playing(pos) => format!("{:?} to play", pos),
+                Turn::Playing(_pos) => "playing",
                 Turn::Endgame => "end",
             }
         )
@@ -36,12 +32,7 @@ impl fmt::Display for Turn {
 
 impl Turn {
     pub fn has_player_pos(&self) -> bool {
-        match self {
-            Self::Pregame => false,
-            Self::Interdeal => false,
-            Self::Endgame => false,
-            _ => true
-        }
+        !matches!(self, Self::Pregame | Self::Interdeal | Self::Endgame)
     }
 
     pub fn from_deal(deal: &Deal) -> Self {
@@ -0,0 +1,66 @@
+//! Drives the computer players of a game.
+use std::sync::Weak;
+use std::time::Duration;
+
+use crate::webgame_server::game::Game;
+use crate::protocol::{Driver, GameMessage, GamePlayerState, GameStateSnapshot, ValleyGameState};
+use crate::storage;
+use crate::dispatcher::send_play_updates;
+
+/// Pause before a computer player plays, so that humans can follow
+const BOT_DELAY: Duration = Duration::from_millis(800);
+
+/// Plays for the computer players of `game` until it has none left or is dropped.
+///
+/// The task outlives the end of a match, so that the bots take part in a rematch.
+pub async fn drive_bots(game: Weak<Game<ValleyGameState, GamePlayerState, GameStateSnapshot, GameMessage>>) {
+    loop {
+        tokio::time::delay_for(BOT_DELAY).await;
+        let game = match game.upgrade() {
+            Some(game) => game,
+            None => return,
+        };
+        let game_state = game.state_handle();
+        let (bot_id, strategy, observation) = {
+            let mut game_state = game_state.lock().await;
+            if game_state.release_driver(Driver::Bots) {
+                return;
+            }
+            match game_state.bot_observation() {
+                Some(found) => found,
+                None => continue,
+            }
+        };
+
+        // Checked when the bot was added
+        let kind = match thevalley_ai::StrategyKind::from_name(&strategy) {
+            Some(kind) => kind,
+            None => {
+                log::error!("bot {} has an unknown strategy {}", bot_id, strategy);
+                return;
+            }
+        };
+
+        // The search may take a while: it runs away from the executor, without holding the game
+        let card = match tokio::task::spawn_blocking(move || {
+            thevalley_ai::strategy(kind).play(&observation)
+        }).await {
+            Ok(card) => card,
+            Err(e) => {
+                log::error!("bot {} failed to choose a card: {}", bot_id, e);
+                continue;
+            }
+        };
+
+        let saving = {
+            let mut game_state = game_state.lock().await;
+            // The game may have changed during the search
+            if game_state.bot_to_play() != Some(bot_id) || game_state.set_play(bot_id, card).is_err() {
+                continue;
+            }
+            send_play_updates(&game, &mut game_state).await;
+            storage::prepare_save(game.id(), &game_state)
+        };
+        saving.write().await;
+    }
+}
@@ -0,0 +1,35 @@
+//! Enforces the time limits of the players.
+use std::sync::Weak;
+use std::time::Duration;
+
+use crate::webgame_server::game::Game;
+use crate::protocol::{Driver, GameMessage, GamePlayerState, GameStateSnapshot, ValleyGameState};
+use crate::storage;
+use crate::dispatcher::send_play_updates;
+
+/// Interval between two checks of the clocks
+const CLOCK_CHECK: Duration = Duration::from_millis(500);
+
+/// Handles the timeouts of `game` until it is dropped or gets unlimited time.
+pub async fn enforce_clocks(game: Weak<Game<ValleyGameState, GamePlayerState, GameStateSnapshot, GameMessage>>) {
+    loop {
+        tokio::time::delay_for(CLOCK_CHECK).await;
+        let game = match game.upgrade() {
+            Some(game) => game,
+            None => return,
+        };
+        let saving = {
+            let game_state = game.state_handle();
+            let mut game_state = game_state.lock().await;
+            if game_state.release_driver(Driver::Clocks) {
+                return;
+            }
+            if !game_state.check_clock() {
+                continue;
+            }
+            send_play_updates(&game, &mut game_state).await;
+            storage::prepare_save(game.id(), &game_state)
+        };
+        saving.write().await;
+    }
+}
@@ -4,27 +4,36 @@ use std::sync::Arc;
 use crate::webgame_server::universe::Universe;
 use crate::webgame_server::game::Game;
 use crate::gprotocol::GameState;
+use crate::storage;
+use crate::bot;
+use crate::clock;
 
 use crate::gprotocol::{ 
     Message, ChatMessage,
-    ProtocolError, ProtocolErrorKind 
+    ProtocolError,
 };
 
 use crate::protocol::{ 
     GamePlayCommand, 
+    ErrorCode,
+    ProtocolError as ValleyError,
     SetPlayerRoleCommand, 
-    PlayEvent,
+    GameMessage,
     PlayCommand,
+    SetOptionsCommand,
+    AddBotCommand,
+    RestoreCommand,
+    Driver,
     ValleyGameState,
     GamePlayerState,
-    GameStateSnapshot
+    GameStateSnapshot,
 };
 
 //see https://users.rust-lang.org/t/how-to-store-async-function-pointer/38343/4
 type DynFut<T> = ::std::pin::Pin<Box<dyn Send + ::std::future::Future<Output = T>>>;
 
 pub fn on_gameplay(
-    universe: Arc<Universe<ValleyGameState, GamePlayerState, GameStateSnapshot, PlayEvent>>,
+    universe: Arc<Universe<ValleyGameState, GamePlayerState, GameStateSnapshot, GameMessage>>,
     user_id: Uuid,
     cmd: GamePlayCommand,
 ) -> DynFut<Result<(), ProtocolError>> {
@@ -32,59 +41,209 @@ pub fn on_gameplay(
         if let Some(game) = universe.get_user_game(user_id).await {
             match cmd {
                 GamePlayCommand::Play(cmd) => on_player_play(game, user_id, cmd).await,
+                GamePlayCommand::SetOptions(cmd) => on_player_set_options(game, user_id, cmd).await,
+                GamePlayCommand::AddBot(cmd) => on_player_add_bot(game, user_id, cmd).await,
+                GamePlayCommand::Resync => on_player_resync(game, user_id).await,
+                GamePlayCommand::Ready => on_player_ready(game, user_id).await,
+                GamePlayCommand::Restore(cmd) => on_player_restore(game, user_id, cmd).await,
             }                        
         } else {
-            Err(ProtocolError::new(
-                    ProtocolErrorKind::BadState,
-                    "not in a game",
-            ))
+            Err(ValleyError::from(ErrorCode::NotInGame).into())
         }
     })
 }                                
 
 pub fn on_player_set_role(
-    universe: Arc<Universe<ValleyGameState, GamePlayerState, GameStateSnapshot, PlayEvent>>,
+    universe: Arc<Universe<ValleyGameState, GamePlayerState, GameStateSnapshot, GameMessage>>,
     user_id: Uuid,
     cmd: SetPlayerRoleCommand,
 ) -> DynFut<Result<(), ProtocolError>> {
     Box::pin(async move {
         if let Some(game) = universe.get_user_game(user_id).await {
-            if !game.is_joinable().await {
-                return Err(ProtocolError::new(
-                        ProtocolErrorKind::BadState,
-                        "cannot set role because game is not not joinable",
-                ));
+            {
+                let game_state = game.state_handle();
+                let mut game_state = game_state.lock().await;
+                if let Err(e) = game_state.check_role_change() {
+                    game.send(user_id, &Message::PlayEvent(GameMessage::Error(e))).await;
+                    return Ok(());
+                }
+                game_state.set_player_role(user_id, cmd.role);
             }
 
-            let game_state = game.state_handle();
-            let mut game_state = game_state.lock().await;
-            game_state.set_player_role(user_id, cmd.role);
-
             game.set_player_not_ready(user_id).await;
             game.broadcast_state().await;
+            let saving = {
+                let game_state = game.state_handle();
+                let game_state = game_state.lock().await;
+                storage::prepare_save(game.id(), &game_state)
+            };
+            saving.write().await;
             Ok(())
         } else {
-            Err(ProtocolError::new(
-                    ProtocolErrorKind::BadState,
-                    "not in a game",
-            ))
+            Err(ValleyError::from(ErrorCode::NotInGame).into())
         }
     })
 }
 
 pub async fn on_player_play(
-    game: Arc<Game<ValleyGameState, GamePlayerState, GameStateSnapshot, PlayEvent>>,
+    game: Arc<Game<ValleyGameState, GamePlayerState, GameStateSnapshot, GameMessage>>,
     player_id: Uuid,
     cmd: PlayCommand,
+) -> Result<(), ProtocolError> {
+        let saving = {
+            let game_state = game.state_handle();
+            let mut game_state = game_state.lock().await;
+            if let Err(e) = game_state.set_play(player_id, cmd.card) {
+                game.send(player_id, &Message::PlayEvent(GameMessage::Error(e))).await;
+                return Ok(());
+            }
+            send_play_updates(&game, &mut game_state).await;
+            storage::prepare_save(game.id(), &game_state)
+        };
+        saving.write().await;
+        Ok(())
+}
+
+/// Sends the events of the last plays to each player, as they see them, or
+/// a full snapshot when the events are not enough.
+pub async fn send_play_updates(
+    game: &Game<ValleyGameState, GamePlayerState, GameStateSnapshot, GameMessage>,
+    game_state: &mut ValleyGameState,
+) {
+    if let Some(events) = game_state.take_events() {
+        let humans: Vec<Uuid> = game_state.get_players().keys()
+            .filter(|id| !game_state.is_bot(**id))
+            .copied()
+            .collect();
+        for player_id in humans {
+            for event in events.iter() {
+                let event = game_state.event_view(player_id, event);
+                game.send(player_id, &Message::PlayEvent(GameMessage::Event(event))).await;
+            }
+        }
+    } else {
+        game.broadcast_state().await;
+    }
+}
+
+pub async fn on_player_resync(
+    game: Arc<Game<ValleyGameState, GamePlayerState, GameStateSnapshot, GameMessage>>,
+    player_id: Uuid,
 ) -> Result<(), ProtocolError> {
         let game_state = game.state_handle();
-        let mut game_state = game_state.lock().await;
-        if let Err(e) = game_state.set_play(player_id, cmd.card) {
-            game.send(player_id, &Message::Error(e.into())).await;
-        } else {
-            game.broadcast(&Message::PlayEvent(PlayEvent::Play ( player_id, cmd.card )))
-            .await;
+        let game_state = game_state.lock().await;
+        let snapshot = game_state.make_snapshot(player_id);
+        game.send(player_id, &Message::GameStateSnapshot(snapshot)).await;
+        Ok(())
+}
+
+pub async fn on_player_ready(
+    game: Arc<Game<ValleyGameState, GamePlayerState, GameStateSnapshot, GameMessage>>,
+    player_id: Uuid,
+) -> Result<(), ProtocolError> {
+        let saving = {
+            let game_state = game.state_handle();
+            let mut game_state = game_state.lock().await;
+            game_state.set_player_ready(player_id);
             game.broadcast_state().await;
-        }
+            storage::prepare_save(game.id(), &game_state)
+        };
+        saving.write().await;
+        Ok(())
+}
+
+pub async fn on_player_set_options(
+    game: Arc<Game<ValleyGameState, GamePlayerState, GameStateSnapshot, GameMessage>>,
+    player_id: Uuid,
+    cmd: SetOptionsCommand,
+) -> Result<(), ProtocolError> {
+        let saving = {
+            let game_state = game.state_handle();
+            let mut game_state = game_state.lock().await;
+            if let Err(e) = game_state.set_options(cmd.options) {
+                game.send(player_id, &Message::PlayEvent(GameMessage::Error(e))).await;
+                return Ok(());
+            }
+            start_drivers(&game, &mut game_state);
+            game.broadcast_state().await;
+            storage::prepare_save(game.id(), &game_state)
+        };
+        saving.write().await;
         Ok(())
 }
+
+pub async fn on_player_add_bot(
+    game: Arc<Game<ValleyGameState, GamePlayerState, GameStateSnapshot, GameMessage>>,
+    player_id: Uuid,
+    cmd: AddBotCommand,
+) -> Result<(), ProtocolError> {
+        let saving = {
+            let game_state = game.state_handle();
+            let mut game_state = game_state.lock().await;
+            if thevalley_ai::StrategyKind::from_name(&cmd.strategy).is_none() {
+                let error = ValleyError::from(ErrorCode::UnknownStrategy);
+                game.send(player_id, &Message::PlayEvent(GameMessage::Error(error))).await;
+                return Ok(());
+            }
+            if let Err(e) = game_state.add_bot(&cmd.strategy) {
+                game.send(player_id, &Message::PlayEvent(GameMessage::Error(e))).await;
+                return Ok(());
+            }
+            start_drivers(&game, &mut game_state);
+            game.broadcast_state().await;
+            storage::prepare_save(game.id(), &game_state)
+        };
+        saving.write().await;
+        Ok(())
+}
+
+/// Resumes in `game` the table `cmd.game_id`, saved before a restart of
+/// the server, where `player_id` had a seat.
+pub async fn on_player_restore(
+    game: Arc<Game<ValleyGameState, GamePlayerState, GameStateSnapshot, GameMessage>>,
+    player_id: Uuid,
+    cmd: RestoreCommand,
+) -> Result<(), ProtocolError> {
+        let saved = match storage::take_game(cmd.game_id, player_id).await {
+            Some(saved) => saved,
+            None => {
+                let error = ValleyError::from(ErrorCode::UnknownSavedGame);
+                game.send(player_id, &Message::PlayEvent(GameMessage::Error(error))).await;
+                return Ok(());
+            }
+        };
+        let saving = {
+            let game_state = game.state_handle();
+            let mut game_state = game_state.lock().await;
+            match game_state.restore(saved.clone()) {
+                Ok(()) => {
+                    log::info!("saved game {} resumed as {}", cmd.game_id, game.id());
+                    start_drivers(&game, &mut game_state);
+                    game.broadcast_state().await;
+                    storage::prepare_save(game.id(), &game_state)
+                }
+                Err(e) => {
+                    game.send(player_id, &Message::PlayEvent(GameMessage::Error(e))).await;
+                    // Keep the saved table for another try
+                    storage::prepare_save(cmd.game_id, &saved)
+                }
+            }
+        };
+        saving.write().await;
+        Ok(())
+}
+
+/// Starts the background tasks `game_state` needs and that are not running yet.
+///
+/// Called whenever a game is set up, changed or restored.
+pub fn start_drivers(
+    game: &Arc<Game<ValleyGameState, GamePlayerState, GameStateSnapshot, GameMessage>>,
+    game_state: &mut ValleyGameState,
+) {
+    if game_state.claim_driver(Driver::Bots) {
+        tokio::spawn(bot::drive_bots(Arc::downgrade(game)));
+    }
+    if game_state.claim_driver(Driver::Clocks) {
+        tokio::spawn(clock::enforce_clocks(Arc::downgrade(game)));
+    }
+}
@@ -1,4 +1,7 @@
+mod bot;
+mod clock;
 mod dispatcher;
+mod storage;
 
 pub(crate) use webgame_server;
 pub(crate) use webgame_protocol as gprotocol;
@@ -16,6 +19,11 @@ pub async fn main() {
     // let name = format!("{}", env!("CARGO_PKG_NAME"));
     let name = env!("CARGO_PKG_NAME");
 
+    // The tables saved before a restart are resumed by their players, from a new table
+    for (game_id, state) in storage::load_games() {
+        log::info!("saved game {} can be resumed at turn {:?}", game_id, state.get_turn());
+    }
+
     webgame_server::launcher::launch(
         name, version, author,
         dispatcher::on_gameplay,
@@ -0,0 +1,327 @@
+//! Storage of the running tables, to survive server restarts.
+//!
+//! The store is chosen with the `THEVALLEY_STORE` environment variable:
+//!
+//! * `file:<directory>` saves each table as a JSON file in the directory ;
+//! * `sqlite:<path>` saves the tables in an SQLite database.
+//!
+//! Nothing is saved when the variable is not set.
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use lazy_static::lazy_static;
+use uuid::Uuid;
+
+use crate::protocol::{Turn, ValleyGameState};
+
+const STORE_VAR: &str = "THEVALLEY_STORE";
+
+/// Error raised by a store.
+#[derive(Debug)]
+pub enum StoreError {
+    /// The `THEVALLEY_STORE` variable can't be understood
+    Config(String),
+    Io(std::io::Error),
+    Sqlite(rusqlite::Error),
+    /// A saved table can't be read back
+    Format(serde_json::Error),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Config(value) => write!(f, "invalid {}: {}", STORE_VAR, value),
+            StoreError::Io(e) => write!(f, "io error: {}", e),
+            StoreError::Sqlite(e) => write!(f, "sqlite error: {}", e),
+            StoreError::Format(e) => write!(f, "invalid saved state: {}", e),
+        }
+    }
+}
+
+impl From<std::io::Error> for StoreError {
+    fn from(e: std::io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        StoreError::Sqlite(e)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(e: serde_json::Error) -> Self {
+        StoreError::Format(e)
+    }
+}
+
+/// Saves and loads the state of tables by game id.
+pub trait GameStore: Send + Sync {
+    fn save(&self, game_id: Uuid, state: &ValleyGameState) -> Result<(), StoreError>;
+    fn remove(&self, game_id: Uuid) -> Result<(), StoreError>;
+    fn load(&self, game_id: Uuid) -> Result<Option<ValleyGameState>, StoreError>;
+    fn load_all(&self) -> Result<Vec<(Uuid, ValleyGameState)>, StoreError>;
+}
+
+/// Stores each table in a `<game id>.json` file.
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(dir: PathBuf) -> Result<Self, StoreError> {
+        fs::create_dir_all(&dir)?;
+        Ok(FileStore { dir })
+    }
+
+    fn path(&self, game_id: Uuid) -> PathBuf {
+        self.dir.join(format!("{}.json", game_id))
+    }
+}
+
+impl GameStore for FileStore {
+    fn save(&self, game_id: Uuid, state: &ValleyGameState) -> Result<(), StoreError> {
+        // Write then rename, so that a crash never leaves a truncated file
+        let tmp_path = self.dir.join(format!("{}.json.tmp", game_id));
+        fs::write(&tmp_path, serde_json::to_vec(state)?)?;
+        fs::rename(&tmp_path, self.path(game_id))?;
+        Ok(())
+    }
+
+    fn remove(&self, game_id: Uuid) -> Result<(), StoreError> {
+        match fs::remove_file(self.path(game_id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn load(&self, game_id: Uuid) -> Result<Option<ValleyGameState>, StoreError> {
+        match fs::read(self.path(game_id)) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn load_all(&self) -> Result<Vec<(Uuid, ValleyGameState)>, StoreError> {
+        let mut games = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension() != Some(std::ffi::OsStr::new("json")) {
+                continue;
+            }
+            let game_id = match path.file_stem().and_then(|stem| stem.to_str()).map(Uuid::parse_str) {
+                Some(Ok(game_id)) => game_id,
+                _ => continue,
+            };
+            let state = serde_json::from_slice(&fs::read(&path)?)?;
+            games.push((game_id, state));
+        }
+        Ok(games)
+    }
+}
+
+/// Stores the tables in the `games` table of an SQLite database.
+pub struct SqliteStore {
+    conn: Mutex<rusqlite::Connection>,
+}
+
+impl SqliteStore {
+    pub fn new(path: PathBuf) -> Result<Self, StoreError> {
+        let conn = rusqlite::Connection::open(path)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS games (id TEXT PRIMARY KEY, state TEXT NOT NULL)",
+            rusqlite::NO_PARAMS,
+        )?;
+        Ok(SqliteStore { conn: Mutex::new(conn) })
+    }
+}
+
+impl GameStore for SqliteStore {
+    fn save(&self, game_id: Uuid, state: &ValleyGameState) -> Result<(), StoreError> {
+        let state = serde_json::to_string(state)?;
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO games (id, state) VALUES (?1, ?2)",
+            &[game_id.to_string(), state],
+        )?;
+        Ok(())
+    }
+
+    fn remove(&self, game_id: Uuid) -> Result<(), StoreError> {
+        self.conn.lock().unwrap().execute("DELETE FROM games WHERE id = ?1", &[game_id.to_string()])?;
+        Ok(())
+    }
+
+    fn load(&self, game_id: Uuid) -> Result<Option<ValleyGameState>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT state FROM games WHERE id = ?1")?;
+        let mut rows = stmt.query_map(&[game_id.to_string()], |row| row.get::<_, String>(0))?;
+        match rows.next() {
+            Some(state) => Ok(Some(serde_json::from_str(&state?)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn load_all(&self) -> Result<Vec<(Uuid, ValleyGameState)>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, state FROM games")?;
+        let rows = stmt.query_map(rusqlite::NO_PARAMS, |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut games = vec![];
+        for row in rows {
+            let (game_id, state) = row?;
+            let game_id = match Uuid::parse_str(&game_id) {
+                Ok(game_id) => game_id,
+                Err(_) => continue,
+            };
+            games.push((game_id, serde_json::from_str(&state)?));
+        }
+        Ok(games)
+    }
+}
+
+/// Creates the store described by `value` (ex: "sqlite:/var/lib/thevalley/games.sqlite").
+// `strip_prefix` is too recent for the pinned toolchain
+#[allow(clippy::manual_strip)]
+pub fn open_store(value: &str) -> Result<Box<dyn GameStore>, StoreError> {
+    const FILE: &str = "file:";
+    const SQLITE: &str = "sqlite:";
+    if value.starts_with(FILE) {
+        Ok(Box::new(FileStore::new(PathBuf::from(&value[FILE.len()..]))?))
+    } else if value.starts_with(SQLITE) {
+        Ok(Box::new(SqliteStore::new(PathBuf::from(&value[SQLITE.len()..]))?))
+    } else {
+        Err(StoreError::Config(value.to_owned()))
+    }
+}
+
+lazy_static! {
+    static ref STORE: Option<Box<dyn GameStore>> = match std::env::var(STORE_VAR) {
+        Ok(value) => match open_store(&value) {
+            Ok(store) => Some(store),
+            Err(e) => {
+                log::error!("games won't be saved: {}", e);
+                None
+            }
+        },
+        Err(_) => None,
+    };
+}
+
+/// Order of the saves of a table.
+#[derive(Default)]
+struct SaveOrder {
+    /// Number of the last copy taken
+    taken: u64,
+    /// Number of the last copy written, the lock serializes the writes
+    written: Arc<Mutex<u64>>,
+}
+
+lazy_static! {
+    static ref SAVES: Mutex<HashMap<Uuid, SaveOrder>> = Mutex::new(HashMap::new());
+}
+
+/// Copy of the state of a table, waiting to be written in the store.
+pub struct PendingSave {
+    game_id: Uuid,
+    number: u64,
+    written: Arc<Mutex<u64>>,
+    state: Option<ValleyGameState>,
+}
+
+/// Copies the state of a table, to write it once the table is unlocked.
+///
+/// Call it while the table is locked: the copies of a table are written
+/// in the order they were taken.
+pub fn prepare_save(game_id: Uuid, state: &ValleyGameState) -> PendingSave {
+    let mut saves = SAVES.lock().unwrap();
+    let order = saves.entry(game_id).or_default();
+    order.taken += 1;
+    PendingSave {
+        game_id,
+        number: order.taken,
+        written: order.written.clone(),
+        state: STORE.as_ref().map(|_| state.clone()),
+    }
+}
+
+impl PendingSave {
+    /// Writes the copy in the configured store, away from the executor.
+    ///
+    /// Finished games are removed from the store. The copy is dropped if a
+    /// more recent one of the table was already written.
+    pub async fn write(self) {
+        let PendingSave { game_id, number, written, state } = self;
+        let state = match state {
+            Some(state) => state,
+            None => return,
+        };
+        let result = tokio::task::spawn_blocking(move || {
+            let mut written = written.lock().unwrap();
+            if *written > number {
+                return;
+            }
+            *written = number;
+            write_game(game_id, &state);
+        }).await;
+        if let Err(e) = result {
+            log::error!("could not save game {}: {}", game_id, e);
+        }
+    }
+}
+
+fn write_game(game_id: Uuid, state: &ValleyGameState) {
+    if let Some(store) = STORE.as_ref() {
+        let result = if state.get_turn() == Turn::Endgame {
+            SAVES.lock().unwrap().remove(&game_id);
+            store.remove(game_id)
+        } else {
+            store.save(game_id, state)
+        };
+        if let Err(e) = result {
+            log::error!("could not save game {}: {}", game_id, e);
+        }
+    }
+}
+
+/// Removes the saved table `game_id` from the store and returns it, if
+/// `player_id` had a seat there.
+pub async fn take_game(game_id: Uuid, player_id: Uuid) -> Option<ValleyGameState> {
+    let store = STORE.as_ref()?;
+    let result = tokio::task::spawn_blocking(move || -> Result<_, StoreError> {
+        let state = match store.load(game_id)? {
+            Some(state) if state.is_seated(player_id) => state,
+            _ => return Ok(None),
+        };
+        store.remove(game_id)?;
+        Ok(Some(state))
+    }).await;
+    match result {
+        Ok(Ok(state)) => state,
+        Ok(Err(e)) => {
+            log::error!("could not load game {}: {}", game_id, e);
+            None
+        }
+        Err(e) => {
+            log::error!("could not load game {}: {}", game_id, e);
+            None
+        }
+    }
+}
+
+/// Returns the tables saved in the configured store.
+pub fn load_games() -> Vec<(Uuid, ValleyGameState)> {
+    match STORE.as_ref().map(|store| store.load_all()) {
+        Some(Ok(games)) => games,
+        Some(Err(e)) => {
+            log::error!("could not load saved games: {}", e);
+            vec![]
+        }
+        None => vec![],
+    }
+}
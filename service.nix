@@ -47,6 +47,14 @@ in
           '';
         };
 
+        store = mkOption {
+          type = types.str;
+          default = "sqlite:/var/lib/thevalley/games.sqlite";
+          description = ''
+            Where running games are saved: "file:<directory>" or "sqlite:<path>".
+          '';
+        };
+
       };
     };
 
@@ -107,7 +115,12 @@ in
           description = "thevalley application server";
           partOf = [ "thevalley.target" ];
 
+          environment = {
+            THEVALLEY_STORE = cfg.store;
+          };
+
           serviceConfig = serviceConfig // { 
+            StateDirectory = "thevalley";
             ExecStart = ''${thevalley}/bin/thevalley_server -d ${thevalley}/front/ \
               -p ${toString cfg.apiPort}'';
           };